@@ -2,6 +2,7 @@ use alloy::{providers::Provider, rpc::types::BlockTransactionsKind};
 use alloy_primitives::{hex::FromHex, B256};
 use anyhow::Result;
 use kona_host::HostCli;
+use kroma_witnessgen::{endpoints::EndpointPool, rollup_config::ResolvedRollupConfig};
 use op_succinct_host_utils::{
     fetcher::{CacheMode, OPSuccinctDataFetcher, RPCMode},
     stats::ExecutionStats,
@@ -9,7 +10,8 @@ use op_succinct_host_utils::{
 };
 use serde_json::Value;
 use sp1_sdk::{block_on, utils as sdk_utils, ExecutionReport};
-use std::{num::ParseIntError, path::PathBuf, str::FromStr};
+use std::{num::ParseIntError, path::PathBuf, str::FromStr, sync::OnceLock};
+use tokio::sync::OnceCell;
 use utils::PreviewReport;
 
 pub mod utils;
@@ -21,9 +23,30 @@ pub fn init_env() {
     sdk_utils::setup_logger();
 }
 
-pub async fn init_fetcher() -> Result<OPSuccinctDataFetcher> {
+/// Reads the endpoints once per process from the env keys, e.g., `L1_RPC`.
+pub fn init_endpoints() -> &'static EndpointPool {
+    static ENDPOINTS: OnceLock<EndpointPool> = OnceLock::new();
+
     init_env();
-    OPSuccinctDataFetcher::new_with_rollup_config().await
+    ENDPOINTS.get_or_init(EndpointPool::from_env)
+}
+
+/// Resolves the rollup config once per process from `ROLLUP_CONFIG_SOURCE` (`rpc`, `embedded` or
+/// the path of a JSON file), or detects it if it is not set.
+pub async fn init_rollup_config() -> Result<&'static ResolvedRollupConfig> {
+    static ROLLUP_CONFIG: OnceCell<ResolvedRollupConfig> = OnceCell::const_new();
+
+    init_env();
+    Ok(ROLLUP_CONFIG
+        .get_or_try_init(|| async {
+            let source = std::env::var("ROLLUP_CONFIG_SOURCE").ok().and_then(|s| s.parse().ok());
+            ResolvedRollupConfig::resolve(init_endpoints(), source).await
+        })
+        .await?)
+}
+
+pub async fn init_fetcher() -> Result<OPSuccinctDataFetcher> {
+    Ok(init_rollup_config().await?.fetcher(init_endpoints())?)
 }
 
 pub fn parse_u64(s: &str) -> Result<u64, ParseIntError> {
@@ -43,9 +66,12 @@ pub async fn get_kroma_host_cli_impl(
         _ => &init_fetcher().await?,
     };
 
-    Ok(fetcher
+    let mut host_cli = fetcher
         .get_host_cli_args(l2_block - 1, l2_block, ProgramType::Single, CacheMode::DeleteCache)
-        .await?)
+        .await?;
+    host_cli.rollup_config_path = Some(init_rollup_config().await?.path.clone());
+
+    Ok(host_cli)
 }
 
 pub async fn get_kroma_host_cli_by_l1_head_hash(
@@ -1,44 +1,231 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
 use kroma_witnessgen::{
-    checker::{assert_if_invalid_rpcs, check_rollup_config_before_mpt_time},
-    interface::{DEFAULT_WITNESSGEN_RPC_ENDPOINT, DEFAULT_WITNESS_STORE_PATH},
+    auth::ApiKeys,
+    chain::{Chain, ChainHandle},
+    checker::{assert_if_invalid_rpcs, check_chain_config, init_http_client, Endpoint},
+    config::{EndpointsConfig, WitnessGenConfig},
+    endpoints::EndpointPool,
+    executor::JobConfig,
+    registry,
+    rollup_config::{ResolvedRollupConfig, RollupConfigSource},
+    telemetry::{init_tracing, TelemetryArgs},
     witness_db::WitnessDB,
 };
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    #[clap(short, long = "endpoint", default_value = DEFAULT_WITNESSGEN_RPC_ENDPOINT)]
-    endpoint: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
 
-    #[clap(short, long = "data", default_value = DEFAULT_WITNESS_STORE_PATH)]
-    data_path: String,
+    /// The TOML file of the configuration. See `witnessgen.example.toml`. The flags below take
+    /// precedence over it.
+    #[clap(long = "config", env = "WITNESSGEN_CONFIG")]
+    config_path: Option<PathBuf>,
+
+    #[clap(short, long = "endpoint")]
+    endpoint: Option<String>,
+
+    #[clap(short, long = "data")]
+    data_path: Option<String>,
+
+    /// The maximum number of L2 blocks a range witness can cover.
+    #[clap(long = "max-range-blocks")]
+    max_range_blocks: Option<u64>,
+
+    /// Returns the generated witness without running it in the SP1 executor (default: `true`).
+    /// `--skip-simulation` is `--skip-simulation=true`, and `--skip-simulation=false` runs it.
+    #[clap(
+        long = "skip-simulation",
+        env = "SKIP_SIMULATION",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    skip_simulation: Option<bool>,
+
+    /// The maximum number of cycles of a witness. A witness exceeding it is marked failed.
+    #[clap(long = "max-cycles")]
+    max_cycles: Option<u64>,
+
+    /// The interval in seconds to check the endpoints. New requests are rejected while any of them
+    /// is unhealthy.
+    #[clap(long = "health-check-interval")]
+    health_check_interval_secs: Option<u64>,
+
+    /// Where the rollup config is read from: `rpc`, `embedded` or the path of a JSON file. If it
+    /// is not given, it is read from `configs/<l2_chain_id>/rollup.json` before the MPT time and
+    /// from L2 Node after it.
+    #[clap(long = "rollup-config", env = "ROLLUP_CONFIG_SOURCE")]
+    rollup_config_source: Option<RollupConfigSource>,
+
+    /// The JSON file of API keys. If it is not given, every call is allowed.
+    #[clap(long = "api-keys")]
+    api_keys_path: Option<String>,
+
+    #[clap(flatten)]
+    telemetry: TelemetryArgs,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    dotenv::dotenv().ok();
-    tracing_subscriber::fmt::Subscriber::builder().init();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compares the rollup config built into the binary with the one of L2 Node for each chain, and
+    /// fails if any field differs.
+    DiffRollupConfig,
+}
+
+impl Args {
+    /// Reads the config file and applies the env vars and the flags over it.
+    fn load_config(&self) -> Result<WitnessGenConfig> {
+        let mut config = match &self.config_path {
+            Some(path) => WitnessGenConfig::from_file(path)?,
+            None => WitnessGenConfig::default(),
+        };
+        config.rpc.merge_env();
+
+        if let Some(endpoint) = &self.endpoint {
+            config.server.endpoint = endpoint.clone();
+        }
+        if let Some(data_path) = &self.data_path {
+            config.storage.data_path = data_path.clone();
+        }
+        if let Some(max_range_blocks) = self.max_range_blocks {
+            config.policies.max_range_blocks = max_range_blocks;
+        }
+        if let Some(skip_simulation) = self.skip_simulation {
+            config.policies.skip_simulation = skip_simulation;
+            if skip_simulation {
+                config.policies.max_cycles = None;
+            }
+        }
+        if let Some(max_cycles) = self.max_cycles {
+            config.policies.max_cycles = Some(max_cycles);
+        }
+        if let Some(interval) = self.health_check_interval_secs {
+            config.timeouts.health_check_interval_secs = interval;
+        }
+        if let Some(source) = &self.rollup_config_source {
+            config.policies.rollup_config = Some(source.clone());
+        }
+        if let Some(api_keys_path) = &self.api_keys_path {
+            config.server.api_keys = Some(api_keys_path.clone());
+        }
+        Ok(config)
+    }
+}
 
+fn main() -> Result<()> {
+    dotenv::dotenv().ok();
     let args = Args::parse();
+    let config = args.load_config()?;
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    if let Some(Command::DiffRollupConfig) = args.command {
+        return runtime.block_on(diff_rollup_config(config));
+    }
+    runtime.block_on(run(args, config))
+}
+
+fn endpoint_pool(rpc: &EndpointsConfig) -> EndpointPool {
+    EndpointPool::new(
+        Endpoint::ALL.into_iter().map(|endpoint| (endpoint, rpc.urls(endpoint).to_vec())).collect(),
+    )
+}
+
+async fn diff_rollup_config(config: WitnessGenConfig) -> Result<()> {
+    init_http_client(Duration::from_secs(config.timeouts.rpc_request_secs));
+
+    let mut num_mismatches = 0;
+    for profile in config.profiles() {
+        let diff = registry::diff_embedded(&endpoint_pool(&profile.rpc)).await?;
+        println!("L2 chain {}: embedded rollup config hash {}", diff.l2_chain_id, diff.hash);
+        if diff.mismatches.is_empty() {
+            println!("  The embedded rollup config matches L2 Node.");
+        }
+        for mismatch in &diff.mismatches {
+            println!(
+                "  {}: {} embedded, {} in L2 Node",
+                mismatch.field, mismatch.expected, mismatch.actual
+            );
+        }
+        num_mismatches += diff.mismatches.len();
+    }
+    if num_mismatches > 0 {
+        bail!("{} field(s) of the embedded rollup configs differ from L2 Node", num_mismatches);
+    }
+    Ok(())
+}
+
+async fn run(args: Args, config: WitnessGenConfig) -> Result<()> {
+    let _tracing_guard = init_tracing(&args.telemetry)?;
+
+    config.validate()?;
+    tracing::info!("Effective config:\n{}", config.to_redacted_toml());
+    init_http_client(Duration::from_secs(config.timeouts.rpc_request_secs));
+
+    let api_keys = config.server.api_keys.as_deref().map(ApiKeys::from_file).transpose()?;
+
+    let profiles = config.profiles();
+    let mut chains = Vec::with_capacity(profiles.len());
+    let mut receivers = Vec::with_capacity(profiles.len());
+    for profile in profiles {
+        // Check if All the RPCs are valid. The endpoints fail over to healthy URLs here, so
+        // everything below is fetched from them.
+        let chain = Arc::new(Chain::new(endpoint_pool(&profile.rpc)));
+        assert_if_invalid_rpcs(&chain).await?;
 
-    // NOTE(Ethan)Before the `MPT time`, the rollup configuration must be read from a JSON file instead of via RPC.
-    // If the launch time is before the MPT time, set `ROLLUP_CONFIG_FROM_FILE` to `true`.
-    check_rollup_config_before_mpt_time().await?;
+        // NOTE: Before the `MPT time`, the rollup configuration must be read from a JSON
+        // file instead of via RPC. It is resolved once here and every data fetcher is created
+        // with it.
+        let rollup_config =
+            Arc::new(ResolvedRollupConfig::resolve(&chain.endpoints, profile.rollup_config).await?);
+        let chain_id = rollup_config.config.l2_chain_id;
+        match profile.chain_id {
+            Some(expected) if expected != chain_id => {
+                bail!("The endpoints of chain {} are of chain {}", expected, chain_id)
+            }
+            _ => {}
+        }
 
-    // Check if All the RPCs are valid.
-    assert_if_invalid_rpcs().await?;
-    tracing::info!("All validation for safe launching has been passed.");
+        check_chain_config(&chain.endpoints, &rollup_config.config).await?;
+        tracing::info!("All validation for safe launching of chain {} has been passed.", chain_id);
 
-    let witness_db = Arc::new(WitnessDB::new(&args.data_path));
-    let (tx, rx) = tokio::sync::mpsc::channel(10);
+        let witness_db = Arc::new(WitnessDB::with_capacity(
+            profile.data_path.as_deref().unwrap_or(&config.storage.data_path),
+            config.storage.capacity,
+            config.storage.value_expiring_secs,
+        ));
+        let (tx, rx) = tokio::sync::mpsc::channel(config.workers.queue_capacity);
+        let job_config = JobConfig {
+            max_range_blocks: config.policies.max_range_blocks,
+            skip_simulation: config.policies.skip_simulation,
+            max_cycles: config.policies.max_cycles,
+            rollup_config,
+            cooldown: Duration::from_millis(config.workers.cooldown_ms),
+        };
+        chains.push(ChainHandle { chain, witness_db, job_config, tx });
+        receivers.push(rx);
+    }
 
-    kroma_witnessgen::executor::run(witness_db.clone(), rx).await;
+    for (handle, rx) in chains.iter().zip(receivers) {
+        kroma_witnessgen::executor::run(
+            handle.chain.clone(),
+            handle.witness_db.clone(),
+            rx,
+            handle.job_config.clone(),
+        )
+        .await;
+    }
+    kroma_witnessgen::monitor::run(
+        chains.clone(),
+        Duration::from_secs(config.timeouts.health_check_interval_secs),
+    )
+    .await;
 
-    kroma_witnessgen::interface::run(witness_db.clone(), tx, args.endpoint).await;
+    kroma_witnessgen::interface::run(chains, config.server.clone(), api_keys).await;
 
     Ok(())
 }
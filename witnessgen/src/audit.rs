@@ -0,0 +1,292 @@
+use alloy_primitives::B256;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::types::TaskInfo;
+
+static AUDIT_FILE_NAME: &str = "audit.jsonl";
+static MAX_FILE_BYTES: u64 = 64 * 1024 * 1024; // 64 MiB
+static MAX_ROTATED_FILES: usize = 7;
+
+/// The maximum number of events returned by a query.
+pub static MAX_QUERY_LIMIT: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventKind {
+    /// A witness is requested.
+    Requested,
+    /// The request is queued to the executor.
+    Queued,
+    /// The request is rejected because another request is in progress or the queue is full.
+    Rejected,
+    /// The executor starts to generate the witness.
+    Started,
+    Completed,
+    Failed,
+    /// A failed witness is removed from the store to be generated again.
+    Removed,
+    /// A witness is evicted by the capacity or the expiration of the store.
+    Evicted,
+}
+
+/// A line of the audit log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Unix timestamp in seconds.
+    pub timestamp: u64,
+    pub kind: AuditEventKind,
+    pub task: TaskInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_req_id: Option<String>,
+    /// The name of the API key which made the call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caller: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(kind: AuditEventKind, task: &TaskInfo) -> Self {
+        let timestamp =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        Self { timestamp, kind, task: task.clone(), user_req_id: None, caller: None, detail: None }
+    }
+
+    pub fn with_user_req_id(mut self, user_req_id: &str) -> Self {
+        self.user_req_id = Some(user_req_id.to_string());
+        self
+    }
+
+    pub fn with_caller(mut self, caller: Option<&str>) -> Self {
+        self.caller = caller.map(str::to_string);
+        self
+    }
+
+    pub fn with_detail<T: ToString>(mut self, detail: T) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    fn matches(&self, query: &AuditQuery) -> bool {
+        let hash_matched = query.hash.map_or(true, |hash| {
+            self.task.l2_hash == hash
+                || self.task.start_l2_hash == hash
+                || self.task.l1_head_hash == hash
+        });
+        hash_matched
+            && query.from.map_or(true, |from| self.timestamp >= from)
+            && query.to.map_or(true, |to| self.timestamp <= to)
+    }
+}
+
+/// The filter of `getAuditLog`. Every condition is optional.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    /// Matches the events of the tasks which have this hash as the L2 block, the first L2 block of
+    /// a range or the L1 head.
+    pub hash: Option<B256>,
+    /// The inclusive range of Unix timestamps in seconds.
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    /// The maximum number of the latest events to return. Defaults to `MAX_QUERY_LIMIT`.
+    pub limit: Option<usize>,
+}
+
+/// An append-only JSONL log of the requests and the jobs. When the file exceeds its limit, it is
+/// rotated to `audit.jsonl.1`, `audit.jsonl.2`, ... and the oldest one is deleted.
+pub struct AuditLog {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    max_rotated_files: usize,
+    file: Mutex<Option<File>>,
+}
+
+impl AuditLog {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self::with_rotation(dir, MAX_FILE_BYTES, MAX_ROTATED_FILES)
+    }
+
+    pub fn with_rotation<P: AsRef<Path>>(
+        dir: P,
+        max_file_bytes: u64,
+        max_rotated_files: usize,
+    ) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_file_bytes,
+            max_rotated_files,
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Appends the event to the log. A failure is logged rather than returned, so that it never
+    /// fails the request or the job.
+    pub fn record(&self, event: AuditEvent) {
+        if let Err(e) = self.append(&event) {
+            tracing::warn!("Failed to write the audit log: {}, event: {:?}", e, event);
+        }
+    }
+
+    fn append(&self, event: &AuditEvent) -> Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            fs::create_dir_all(&self.dir)?;
+            *file = Some(OpenOptions::new().create(true).append(true).open(self.path(0))?);
+        }
+
+        let len = file.as_ref().unwrap().metadata()?.len();
+        if len > 0 && len + line.len() as u64 > self.max_file_bytes {
+            *file = None;
+            self.rotate()?;
+            *file = Some(OpenOptions::new().create(true).append(true).open(self.path(0))?);
+        }
+
+        let file = file.as_mut().unwrap();
+        file.write_all(&line)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            self.dir.join(AUDIT_FILE_NAME)
+        } else {
+            self.dir.join(format!("{}.{}", AUDIT_FILE_NAME, index))
+        }
+    }
+
+    fn rotate(&self) -> Result<()> {
+        let oldest = self.path(self.max_rotated_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for index in (0..self.max_rotated_files).rev() {
+            let path = self.path(index);
+            if path.exists() {
+                fs::rename(&path, self.path(index + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the latest events matching the query, from the oldest to the latest.
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEvent>> {
+        let limit = query.limit.unwrap_or(MAX_QUERY_LIMIT).min(MAX_QUERY_LIMIT);
+        let mut events = VecDeque::with_capacity(limit);
+
+        // Hold the lock so that the files are not rotated while being read.
+        let _file = self.file.lock().unwrap();
+        for index in (0..=self.max_rotated_files).rev() {
+            let path = self.path(index);
+            if !path.exists() {
+                continue;
+            }
+            let file = File::open(&path)
+                .map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                // Skip a broken line, e.g., one cut by a crash.
+                let Ok(event) = serde_json::from_str::<AuditEvent>(&line) else {
+                    continue;
+                };
+                if !event.matches(query) {
+                    continue;
+                }
+                if events.len() == limit {
+                    events.pop_front();
+                }
+                events.push_back(event);
+            }
+        }
+        Ok(events.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::b256;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("witnessgen-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let dir = temp_dir("audit-query");
+        let audit_log = AuditLog::new(&dir);
+
+        let l1_head = b256!("0000000000000000000000000000000000000000000000000000000000000003");
+        let task1 = TaskInfo::new(
+            b256!("0000000000000000000000000000000000000000000000000000000000000001"),
+            l1_head,
+        );
+        let task2 = TaskInfo::new(
+            b256!("0000000000000000000000000000000000000000000000000000000000000002"),
+            l1_head,
+        );
+        audit_log.record(AuditEvent::new(AuditEventKind::Requested, &task1).with_caller(Some("a")));
+        audit_log.record(AuditEvent::new(AuditEventKind::Requested, &task2));
+        audit_log.record(AuditEvent::new(AuditEventKind::Completed, &task1));
+
+        let query = AuditQuery { hash: Some(task1.l2_hash), ..Default::default() };
+        let events = audit_log.query(&query).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, AuditEventKind::Requested);
+        assert_eq!(events[0].caller.as_deref(), Some("a"));
+        assert_eq!(events[1].kind, AuditEventKind::Completed);
+
+        // The L1 head matches both of the tasks and only the latest one is returned.
+        let query = AuditQuery { hash: Some(l1_head), limit: Some(1), ..Default::default() };
+        assert_eq!(audit_log.query(&query).unwrap()[0].kind, AuditEventKind::Completed);
+
+        let query = AuditQuery { to: Some(0), ..Default::default() };
+        assert!(audit_log.query(&query).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = temp_dir("audit-rotation");
+        // Every event exceeds the limit, so each of them is written to its own file.
+        let audit_log = AuditLog::with_rotation(&dir, 1, 2);
+
+        let task = TaskInfo::default();
+        for kind in [AuditEventKind::Requested, AuditEventKind::Queued, AuditEventKind::Started] {
+            audit_log.record(AuditEvent::new(kind, &task));
+        }
+        audit_log.record(AuditEvent::new(AuditEventKind::Completed, &task));
+
+        assert!(audit_log.path(2).exists());
+        assert!(!audit_log.path(3).exists());
+        // The first event has been deleted with the oldest file.
+        let kinds: Vec<_> = audit_log
+            .query(&AuditQuery::default())
+            .unwrap()
+            .into_iter()
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![AuditEventKind::Queued, AuditEventKind::Started, AuditEventKind::Completed]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
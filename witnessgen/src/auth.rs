@@ -0,0 +1,226 @@
+use anyhow::{anyhow, Result};
+use jsonrpc_core::{
+    futures_util::future::{self, Either},
+    Call, Metadata, Middleware, Output,
+};
+use jsonrpc_http_server::hyper::{header::AUTHORIZATION, Body, Request};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{fs::File, future::Future, io::BufReader, sync::Arc};
+
+use crate::errors::WitnessGenError;
+
+/// Sensitive methods which can only be called with a key of the `admin` scope.
+pub const ADMIN_METHODS: &[&str] = &["getAuditLog", "getServerStatus"];
+
+const API_KEY_HEADER: &str = "x-api-key";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    User,
+    Admin,
+}
+
+impl Scope {
+    pub fn required_for(method: &str) -> Self {
+        if ADMIN_METHODS.contains(&method) {
+            Scope::Admin
+        } else {
+            Scope::User
+        }
+    }
+
+    pub fn allows(&self, required: Scope) -> bool {
+        *self == Scope::Admin || required == Scope::User
+    }
+}
+
+/// An API key loaded from the key file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// The name of the key holder, which is used for logging.
+    pub name: String,
+    pub token: String,
+    pub scope: Scope,
+}
+
+/// The set of API keys accepted by the server.
+///
+/// The key file is a JSON array such as
+/// `[{ "name": "validator", "token": "<TOKEN>", "scope": "user" }]`.
+#[derive(Clone, Debug)]
+pub struct ApiKeys {
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<ApiKey>) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(anyhow!("No API key is given"));
+        }
+        for (i, key) in keys.iter().enumerate() {
+            if key.token.is_empty() {
+                return Err(anyhow!("The token of {:?} is empty", key.name));
+            }
+            if keys[..i].iter().any(|other| other.token == key.token) {
+                return Err(anyhow!("The token of {:?} is duplicated", key.name));
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self> {
+        let file = File::open(path).map_err(|e| anyhow!("Failed to open {}: {}", path, e))?;
+        let keys: Vec<ApiKey> = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path, e))?;
+        Self::new(keys)
+    }
+
+    fn find(&self, token: &str) -> Option<&ApiKey> {
+        self.keys.iter().find(|key| constant_time_eq(key.token.as_bytes(), token.as_bytes()))
+    }
+
+    /// Returns the key which is allowed to call `method` with `token`.
+    pub fn authorize(&self, token: Option<&str>, method: &str) -> Result<&ApiKey, WitnessGenError> {
+        let key = token.and_then(|token| self.find(token)).ok_or_else(|| {
+            WitnessGenError::unauthorized("Missing or invalid API key".to_string())
+                .with_params(json!({ "method": method }))
+        })?;
+
+        let required = Scope::required_for(method);
+        if !key.scope.allows(required) {
+            return Err(WitnessGenError::unauthorized(format!(
+                "{} requires the {:?} scope",
+                method, required
+            ))
+            .with_params(json!({ "method": method })));
+        }
+        Ok(key)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The request metadata carrying the token of the caller.
+#[derive(Clone, Debug, Default)]
+pub struct AuthMeta {
+    pub token: Option<String>,
+    /// The name of the API key, which is set once the call is authorized.
+    pub caller: Option<String>,
+}
+
+impl Metadata for AuthMeta {}
+
+/// Reads the token from either `Authorization: Bearer <TOKEN>` or `X-API-Key: <TOKEN>`.
+pub fn read_auth_meta(req: &Request<Body>) -> AuthMeta {
+    let headers = req.headers();
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let api_key = headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+
+    AuthMeta { token: bearer.or(api_key).map(|token| token.trim().to_string()), caller: None }
+}
+
+/// Rejects the calls which are not authorized by the API keys. Every call is allowed if no key is
+/// configured.
+#[derive(Clone, Debug, Default)]
+pub struct AuthMiddleware {
+    api_keys: Option<Arc<ApiKeys>>,
+}
+
+impl AuthMiddleware {
+    pub fn new(api_keys: Option<ApiKeys>) -> Self {
+        Self { api_keys: api_keys.map(Arc::new) }
+    }
+}
+
+impl Middleware<AuthMeta> for AuthMiddleware {
+    type Future = future::Ready<Option<jsonrpc_core::Response>>;
+    type CallFuture = future::Ready<Option<Output>>;
+
+    fn on_call<F, X>(&self, call: Call, mut meta: AuthMeta, next: F) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, AuthMeta) -> X + Send + Sync,
+        X: Future<Output = Option<Output>> + Send + 'static,
+    {
+        let api_keys = match &self.api_keys {
+            Some(api_keys) => api_keys,
+            None => return Either::Right(next(call, meta)),
+        };
+
+        let (method, id, jsonrpc) = match &call {
+            Call::MethodCall(method_call) => {
+                (&method_call.method, method_call.id.clone(), method_call.jsonrpc)
+            }
+            Call::Notification(notification) => {
+                (&notification.method, jsonrpc_core::Id::Null, notification.jsonrpc)
+            }
+            Call::Invalid { .. } => return Either::Right(next(call, meta)),
+        };
+
+        match api_keys.authorize(meta.token.as_deref(), method) {
+            Ok(key) => {
+                tracing::debug!("Authorized {:?} to call {:?}", key.name, method);
+                meta.caller = Some(key.name.clone());
+                Either::Right(next(call, meta))
+            }
+            Err(e) => {
+                tracing::warn!("Rejected a call to {:?}: {}", method, e.message.as_ref().unwrap());
+                let output = match call {
+                    Call::Notification(_) => None,
+                    _ => Some(Output::from(Err(e.to_json_error()), id, jsonrpc)),
+                };
+                Either::Left(future::ready(output))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorCode;
+
+    fn api_keys() -> ApiKeys {
+        ApiKeys::new(vec![
+            ApiKey { name: "validator".into(), token: "user-token".into(), scope: Scope::User },
+            ApiKey { name: "operator".into(), token: "admin-token".into(), scope: Scope::Admin },
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_authorize() {
+        let api_keys = api_keys();
+
+        assert_eq!(
+            api_keys.authorize(Some("user-token"), "requestWitness").unwrap().name,
+            "validator"
+        );
+        assert_eq!(
+            api_keys.authorize(Some("admin-token"), "getAuditLog").unwrap().name,
+            "operator"
+        );
+
+        let err = api_keys.authorize(Some("user-token"), "getAuditLog").unwrap_err();
+        assert_eq!(err.code, ErrorCode::Unauthorized);
+        let err = api_keys.authorize(Some("user-token"), "getServerStatus").unwrap_err();
+        assert_eq!(err.code, ErrorCode::Unauthorized);
+        let err = api_keys.authorize(Some("wrong-token"), "requestWitness").unwrap_err();
+        assert_eq!(err.code, ErrorCode::Unauthorized);
+        let err = api_keys.authorize(None, "spec").unwrap_err();
+        assert_eq!(err.code, ErrorCode::Unauthorized);
+    }
+
+    #[test]
+    fn test_duplicated_token() {
+        let key = ApiKey { name: "validator".into(), token: "token".into(), scope: Scope::User };
+        assert!(ApiKeys::new(vec![key.clone(), key]).is_err());
+        assert!(ApiKeys::new(vec![]).is_err());
+    }
+}
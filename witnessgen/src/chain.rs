@@ -0,0 +1,40 @@
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    endpoints::EndpointPool, executor::JobConfig, status::StatusTracker, types::QueuedTask,
+    witness_db::WitnessDB,
+};
+
+/// An L2 chain served by the witness generator. Every check of the chain is made with its endpoints
+/// and recorded to its status, so the chains served by a process never affect each other.
+pub struct Chain {
+    pub endpoints: EndpointPool,
+    pub status: StatusTracker,
+}
+
+impl Chain {
+    pub fn new(endpoints: EndpointPool) -> Self {
+        Self { endpoints, status: StatusTracker::new() }
+    }
+
+    /// Creates the chain of the endpoints given by the env keys, e.g., `L1_RPC`.
+    pub fn from_env() -> Self {
+        Self::new(EndpointPool::from_env())
+    }
+}
+
+/// A chain served by the RPC server along with its store and the queue of its executor.
+#[derive(Clone)]
+pub struct ChainHandle {
+    pub chain: Arc<Chain>,
+    pub witness_db: Arc<WitnessDB>,
+    pub job_config: JobConfig,
+    pub tx: Sender<QueuedTask>,
+}
+
+impl ChainHandle {
+    pub fn chain_id(&self) -> u64 {
+        self.job_config.rollup_config.config.l2_chain_id
+    }
+}
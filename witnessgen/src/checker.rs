@@ -1,17 +1,99 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::env;
+use std::{
+    collections::BTreeMap,
+    env,
+    future::Future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use alloy_consensus::Header;
-use alloy_primitives::Bytes;
+use alloy_primitives::{Bytes, B256};
 use alloy_rlp::Decodable;
-use op_succinct_host_utils::fetcher::{OPSuccinctDataFetcher, RPCMode};
+use op_alloy_genesis::RollupConfig;
 
-pub async fn fetch_l2_rpc_data(method: &str, params: Vec<Value>) -> Result<Value> {
-    let l2_rpc_url = env::var("L2_RPC").expect("L2_RPC is not set");
-    let client = reqwest::Client::new();
-    let response = client
-        .post(l2_rpc_url)
+use crate::{
+    chain::{Chain, ChainHandle},
+    endpoints::EndpointPool,
+    errors::WitnessGenError,
+    history::{check_history_window, check_within_history},
+    metrics::METRICS,
+    registry,
+    status::StatusTracker,
+    types::TaskInfo,
+};
+
+static DEFAULT_NATIVE_HOST_RUNNER_PATH: &str =
+    "target/native_host_runner/release/native_host_runner";
+static ROLLUP_CONFIGS_DIR: &str = "configs";
+pub static DEFAULT_RPC_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+static HTTP_CLIENT: OnceCell<reqwest::Client> = OnceCell::new();
+
+/// The external endpoints the witness generator depends on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endpoint {
+    L1,
+    L1Beacon,
+    L2,
+    L2Node,
+}
+
+impl Endpoint {
+    pub const ALL: [Endpoint; 4] =
+        [Endpoint::L1, Endpoint::L1Beacon, Endpoint::L2, Endpoint::L2Node];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Endpoint::L1 => "l1",
+            Endpoint::L1Beacon => "l1_beacon",
+            Endpoint::L2 => "l2",
+            Endpoint::L2Node => "l2_node",
+        }
+    }
+
+    pub fn env_key(&self) -> &'static str {
+        match *self {
+            Endpoint::L1 => "L1_RPC",
+            Endpoint::L1Beacon => "L1_BEACON_RPC",
+            Endpoint::L2 => "L2_RPC",
+            Endpoint::L2Node => "L2_NODE_RPC",
+        }
+    }
+}
+
+pub async fn fetch_rpc_data(
+    endpoints: &EndpointPool,
+    endpoint: Endpoint,
+    method: &str,
+    params: Vec<Value>,
+) -> Result<Value> {
+    fetch_rpc_data_at(&endpoints.url(endpoint)?, method, params).await
+}
+
+/// Sets the timeout of every request to the endpoints. It takes effect only if called before the
+/// first request.
+pub fn init_http_client(timeout: Duration) {
+    let client = reqwest::Client::builder().timeout(timeout).build().unwrap();
+    let _ = HTTP_CLIENT.set(client);
+}
+
+fn http_client() -> &'static reqwest::Client {
+    HTTP_CLIENT.get_or_init(|| {
+        let timeout = Duration::from_secs(DEFAULT_RPC_REQUEST_TIMEOUT_SECS);
+        reqwest::Client::builder().timeout(timeout).build().unwrap()
+    })
+}
+
+// NOTE: The URL is left out of the errors of the requests, since it may contain an API key and the
+// errors are served by `/readyz` and `getServerStatus`.
+async fn fetch_rpc_data_at(url: &str, method: &str, params: Vec<Value>) -> Result<Value> {
+    let response = http_client()
+        .post(url)
         .json(&json!({
             "jsonrpc": "2.0",
             "method": method,
@@ -19,9 +101,11 @@ pub async fn fetch_l2_rpc_data(method: &str, params: Vec<Value>) -> Result<Value
             "id": 1
         }))
         .send()
-        .await?
+        .await
+        .map_err(reqwest::Error::without_url)?
         .json::<serde_json::Value>()
-        .await?;
+        .await
+        .map_err(reqwest::Error::without_url)?;
 
     if let Some(error) = response.get("error") {
         let error_message = error["message"].as_str().unwrap_or("Unknown error");
@@ -31,92 +115,886 @@ pub async fn fetch_l2_rpc_data(method: &str, params: Vec<Value>) -> Result<Value
     serde_json::from_value(response["result"].clone()).map_err(Into::into)
 }
 
-pub async fn check_rollup_config_before_mpt_time() -> Result<()> {
-    dotenv::dotenv().ok();
+pub async fn fetch_l2_rpc_data(
+    endpoints: &EndpointPool,
+    method: &str,
+    params: Vec<Value>,
+) -> Result<Value> {
+    fetch_rpc_data(endpoints, Endpoint::L2, method, params).await
+}
+
+/// Fetches `path` of the beacon API of the L1 beacon node.
+pub async fn fetch_beacon_data(endpoints: &EndpointPool, path: &str) -> Result<Value> {
+    fetch_beacon_data_at(&endpoints.url(Endpoint::L1Beacon)?, path).await
+}
+
+async fn fetch_beacon_data_at(beacon_url: &str, path: &str) -> Result<Value> {
+    let url = format!("{}/{}", beacon_url.trim_end_matches('/'), path);
+    let response = http_client()
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(reqwest::Error::without_url)?;
+    Ok(response.json::<Value>().await.map_err(reqwest::Error::without_url)?)
+}
+
+pub(crate) fn rpc_check_failed(endpoint: Endpoint, message: String) -> WitnessGenError {
+    METRICS.rpc_check_failures.with_label_values(&[endpoint.name()]).inc();
+    WitnessGenError::rpc_unavailable(message).with_params(json!({ "endpoint": endpoint.name() }))
+}
+
+fn blob_check_failed(message: String, slot: u64) -> WitnessGenError {
+    METRICS.rpc_check_failures.with_label_values(&[Endpoint::L1Beacon.name()]).inc();
+    WitnessGenError::blobs_unavailable(message)
+        .with_params(json!({ "endpoint": Endpoint::L1Beacon.name(), "slot": slot }))
+}
+
+/// Records the result of the check of `endpoint` to be reported by `getServerStatus`.
+fn record_check<T>(
+    status: &StatusTracker,
+    endpoint: Endpoint,
+    result: Result<T, WitnessGenError>,
+) -> Result<T, WitnessGenError> {
+    let error = result
+        .as_ref()
+        .err()
+        .map(|e| e.message.clone().unwrap_or_else(|| e.code.kind().to_string()));
+    status.record_check(endpoint.name(), error);
+    result
+}
 
-    let chain_config = fetch_l2_rpc_data("debug_chainConfig", vec![]).await?;
-    if chain_config.get("optimism").is_none() {
-        env::set_var("ROLLUP_CONFIG_FROM_FILE", "true");
-        println!("It should be before MPT time. `ROLLUP_CONFIG_FROM_FILE` is set as true.");
+/// Checks every URL of `endpoint` with `check` and switches the endpoint to the first healthy one.
+/// Returns the result of the URL the endpoint is served by.
+async fn check_urls<T, F, Fut>(
+    chain: &Chain,
+    endpoint: Endpoint,
+    check: F,
+) -> Result<T, WitnessGenError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<T, WitnessGenError>>,
+{
+    let urls = chain.endpoints.urls(endpoint);
+    let mut results = Vec::with_capacity(urls.len());
+    for url in urls {
+        results.push(check(url).await);
     }
-    let _ = OPSuccinctDataFetcher::new_with_rollup_config().await.unwrap();
+    chain.endpoints.update(endpoint, results.iter().map(Result::is_ok).collect());
+
+    let result = match results.iter().position(Result::is_ok) {
+        Some(i) => results.swap_remove(i),
+        None if !results.is_empty() => results.swap_remove(0),
+        None => Err(rpc_check_failed(endpoint, format!("{} is not set", endpoint.env_key()))),
+    };
+    record_check(&chain.status, endpoint, result)
+}
+
+/// Checks if L1 Geth at `url` is alive and in debug mode.
+pub async fn check_l1(url: String) -> Result<(), WitnessGenError> {
+    let _ = fetch_rpc_data_at(&url, "net_version", vec![])
+        .await
+        .map_err(|e| rpc_check_failed(Endpoint::L1, format!("L1 Geth is not alive: {}", e)))?;
+
+    let _ = fetch_rpc_data_at(&url, "debug_getRawHeader", vec!["latest".into()]).await.map_err(
+        |e| rpc_check_failed(Endpoint::L1, format!("L1 Geth is not in debug mode: {}", e)),
+    )?;
 
     Ok(())
 }
 
-pub async fn assert_if_invalid_rpcs() -> Result<()> {
-    dotenv::dotenv().ok();
-    let fetcher = OPSuccinctDataFetcher::new_with_rollup_config().await.unwrap();
+/// Checks if L2 Geth at `url` is alive and in debug mode, and returns the latest L2 block number.
+pub async fn check_l2(url: String) -> Result<u64, WitnessGenError> {
+    let _ = fetch_rpc_data_at(&url, "net_version", vec![])
+        .await
+        .map_err(|e| rpc_check_failed(Endpoint::L2, format!("L2 Geth is not alive: {}", e)))?;
+
+    let raw_header = fetch_rpc_data_at(&url, "debug_getRawHeader", vec!["latest".into()])
+        .await
+        .and_then(|value| Ok(serde_json::from_value::<Bytes>(value)?))
+        .map_err(|e| {
+            rpc_check_failed(Endpoint::L2, format!("L2 Geth is not in debug mode: {}", e))
+        })?;
+    let header = Header::decode(&mut raw_header.as_ref())
+        .map_err(|e| WitnessGenError::internal(format!("Failed to decode header: {e}")))?;
+
+    Ok(header.number)
+}
+
+/// Checks if L2 Node at `url` is alive by fetching the output at the parent of `l2_number`.
+pub async fn check_l2_node(url: String, l2_number: u64) -> Result<(), WitnessGenError> {
+    let block_number = format!("0x{:x}", l2_number - 1);
+    let _ = fetch_rpc_data_at(&url, "optimism_outputAtBlock", vec![block_number.into()])
+        .await
+        .map_err(|e| rpc_check_failed(Endpoint::L2Node, format!("L2 Node is not alive: {}", e)))?;
+
+    Ok(())
+}
+
+/// The network and the blob retention of the L1 beacon.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BeaconInfo {
+    pub genesis_time: u64,
+    pub seconds_per_slot: u64,
+    /// The chain ID of the execution layer the beacon is following.
+    pub chain_id: u64,
+    pub head_slot: u64,
+    /// The oldest slot at which the blob sidecars were found to be served.
+    pub oldest_blob_slot: u64,
+    /// How far back from the head the blobs are retained in seconds.
+    pub blob_retention_secs: u64,
+}
+
+// Reads the integer at `pointer`, which the beacon API encodes as a decimal string.
+pub(crate) fn json_u64(value: &Value, pointer: &str) -> Result<u64> {
+    let field = value.pointer(pointer).ok_or_else(|| anyhow::anyhow!("{} is missing", pointer))?;
+    match field {
+        Value::String(s) => Ok(s.parse::<u64>()?),
+        _ => field.as_u64().ok_or_else(|| anyhow::anyhow!("{} is not an integer", pointer)),
+    }
+}
 
-    // Check if L1 Geth is alive.
-    let _: Value = fetcher
-        .fetch_rpc_data_with_mode(RPCMode::L1, "net_version", vec![])
+// The JSON-RPC API encodes the integers as hex quantities.
+pub(crate) fn quantity_u64(value: &Value) -> Result<u64> {
+    let s = value.as_str().ok_or_else(|| anyhow::anyhow!("{} is not a quantity", value))?;
+    Ok(u64::from_str_radix(s.trim_start_matches("0x"), 16)?)
+}
+
+/// Checks if L1 Beacon at `url` is alive, follows the same network as L1 Geth and serves the blob
+/// sidecars from the head back to the retention period of the spec.
+pub async fn check_l1_beacon(
+    endpoints: &EndpointPool,
+    url: String,
+) -> Result<BeaconInfo, WitnessGenError> {
+    let beacon_failed = |message: &str, e: anyhow::Error| {
+        rpc_check_failed(Endpoint::L1Beacon, format!("{}: {}", message, e))
+    };
+
+    let genesis_time = fetch_beacon_data_at(&url, "eth/v1/beacon/genesis")
         .await
-        .expect("L1 Geth is not alive");
+        .and_then(|genesis| json_u64(&genesis, "/data/genesis_time"))
+        .map_err(|e| beacon_failed("L1 Beacon is not alive", e))?;
 
-    // Check if L2 Geth is alive.
-    let _: Value = fetcher
-        .fetch_rpc_data_with_mode(RPCMode::L2, "net_version", vec![])
+    // Check the network of the beacon matches the one of L1 Geth.
+    let chain_id = fetch_beacon_data_at(&url, "eth/v1/config/deposit_contract")
         .await
-        .expect("L2 Geth is not alive");
+        .and_then(|deposit_contract| json_u64(&deposit_contract, "/data/chain_id"))
+        .map_err(|e| beacon_failed("Failed to fetch the network of L1 Beacon", e))?;
+    let l1_chain_id = fetch_rpc_data(endpoints, Endpoint::L1, "eth_chainId", vec![])
+        .await
+        .and_then(|chain_id| quantity_u64(&chain_id))
+        .map_err(|e| rpc_check_failed(Endpoint::L1, format!("L1 Geth is not alive: {}", e)))?;
+    if chain_id != l1_chain_id {
+        return Err(rpc_check_failed(
+            Endpoint::L1Beacon,
+            format!(
+                "L1 Beacon follows chain {} but L1 Geth serves chain {}",
+                chain_id, l1_chain_id
+            ),
+        )
+        .with_params(json!({
+            "endpoint": Endpoint::L1Beacon.name(),
+            "beacon_chain_id": chain_id,
+            "l1_chain_id": l1_chain_id,
+        })));
+    }
 
-    // Check if L1 Geth is in debug mode
-    let _: Value = fetcher
-        .fetch_rpc_data_with_mode(RPCMode::L1, "debug_getRawHeader", vec!["latest".into()])
+    let spec = fetch_beacon_data_at(&url, "eth/v1/config/spec")
+        .await
+        .map_err(|e| beacon_failed("Failed to fetch the spec of L1 Beacon", e))?;
+    let spec_u64 = |key: &str| {
+        json_u64(&spec, &format!("/data/{}", key))
+            .map_err(|e| beacon_failed("Invalid spec of L1 Beacon", e))
+    };
+    let seconds_per_slot = spec_u64("SECONDS_PER_SLOT")?;
+    let slots_per_epoch = spec_u64("SLOTS_PER_EPOCH")?;
+    let min_epochs_for_blobs = spec_u64("MIN_EPOCHS_FOR_BLOB_SIDECARS_REQUESTS")?;
+    let head_slot = fetch_beacon_data_at(&url, "eth/v1/beacon/headers/head")
         .await
-        .expect("L1 Geth is not in debug mode");
+        .and_then(|head| json_u64(&head, "/data/header/message/slot"))
+        .map_err(|e| beacon_failed("Failed to fetch the head of L1 Beacon", e))?;
 
-    // Check L2 Geth is in debug mode
-    let raw_header: Bytes = fetcher
-        .fetch_rpc_data_with_mode(RPCMode::L2, "debug_getRawHeader", vec!["latest".into()])
+    // Check the blob sidecars of a recent block are served.
+    fetch_beacon_data_at(&url, &format!("eth/v1/beacon/blob_sidecars/{}", head_slot))
         .await
-        .expect("L2 Geth is not in debug mode");
+        .map_err(|e| {
+            blob_check_failed(
+                format!("L1 Beacon does not serve the blobs at the head: {}", e),
+                head_slot,
+            )
+        })?;
 
-    // Check if L2 Node is alive.
-    let header = Header::decode(&mut raw_header.as_ref())
-        .map_err(|e| anyhow!("Failed to decode header: {e}"))?;
-    let latest = format!("0x{:x}", header.number - 1);
-    let _: Value = fetcher
-        .fetch_rpc_data_with_mode(RPCMode::L2Node, "optimism_outputAtBlock", vec![latest.into()])
+    // NOTE: The blobs are pruned by epoch, so the oldest slot is probed an epoch after the
+    // retention boundary. Since a slot may have no block, the following slots of the epoch are
+    // probed as well.
+    let retention_slots = min_epochs_for_blobs * slots_per_epoch;
+    let boundary_slot = head_slot.saturating_sub(retention_slots.saturating_sub(slots_per_epoch));
+    let mut oldest_blob_slot = None;
+    for slot in boundary_slot..(boundary_slot + slots_per_epoch).min(head_slot + 1) {
+        if fetch_beacon_data_at(&url, &format!("eth/v1/beacon/blob_sidecars/{}", slot))
+            .await
+            .is_ok()
+        {
+            oldest_blob_slot = Some(slot);
+            break;
+        }
+    }
+    let oldest_blob_slot = oldest_blob_slot.ok_or_else(|| {
+        blob_check_failed(
+            format!(
+                "L1 Beacon does not retain the blobs of the last {} epochs",
+                min_epochs_for_blobs
+            ),
+            boundary_slot,
+        )
+    })?;
+
+    let beacon_info = BeaconInfo {
+        genesis_time,
+        seconds_per_slot,
+        chain_id,
+        head_slot,
+        oldest_blob_slot,
+        blob_retention_secs: (head_slot - oldest_blob_slot) * seconds_per_slot,
+    };
+    tracing::info!("L1 Beacon: {:?}", beacon_info);
+
+    Ok(beacon_info)
+}
+
+/// A block of the L2 chain reported by the L2 node.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct L2BlockRef {
+    pub hash: B256,
+    pub number: u64,
+}
+
+/// The heads of the L2 chain reported by `optimism_syncStatus`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SyncStatus {
+    pub safe_l2: L2BlockRef,
+    pub finalized_l2: L2BlockRef,
+}
+
+/// Returns the number of the L2 block of `l2_hash`.
+async fn fetch_l2_block_number(
+    endpoints: &EndpointPool,
+    l2_hash: B256,
+) -> Result<u64, WitnessGenError> {
+    let params = vec![json!(l2_hash), json!(false)];
+    let block = fetch_rpc_data(endpoints, Endpoint::L2, "eth_getBlockByHash", params)
+        .await
+        .map_err(|e| rpc_check_failed(Endpoint::L2, format!("L2 Geth is not alive: {}", e)))?;
+    block.get("number").map(quantity_u64).transpose()?.ok_or_else(|| {
+        WitnessGenError::l2_block_not_found(format!("L2 block {} is not found", l2_hash))
+            .with_params(json!({ "l2_hash": l2_hash }))
+    })
+}
+
+/// Returns the number of the L1 block of `l1_head_hash` if it is on the canonical chain.
+async fn fetch_canonical_l1_number(
+    endpoints: &EndpointPool,
+    l1_head_hash: B256,
+) -> Result<u64, WitnessGenError> {
+    let l1_head_not_found = |message: String| {
+        WitnessGenError::l1_head_not_found(message)
+            .with_params(json!({ "l1_head_hash": l1_head_hash }))
+    };
+    let fetch_block = |method: &'static str, param: Value| async move {
+        fetch_rpc_data(endpoints, Endpoint::L1, method, vec![param, json!(false)])
+            .await
+            .map_err(|e| rpc_check_failed(Endpoint::L1, format!("L1 Geth is not alive: {}", e)))
+    };
+
+    let block = fetch_block("eth_getBlockByHash", json!(l1_head_hash)).await?;
+    let l1_number = block
+        .get("number")
+        .map(quantity_u64)
+        .transpose()?
+        .ok_or_else(|| l1_head_not_found(format!("L1 head {} is not found", l1_head_hash)))?;
+    let canonical_block =
+        fetch_block("eth_getBlockByNumber", json!(format!("0x{:x}", l1_number))).await?;
+    let canonical_hash = canonical_block.get("hash").and_then(|hash| B256::deserialize(hash).ok());
+    if canonical_hash != Some(l1_head_hash) {
+        return Err(l1_head_not_found(format!(
+            "L1 head {} is not on the canonical chain",
+            l1_head_hash
+        )));
+    }
+
+    Ok(l1_number)
+}
+
+/// The output of an L2 block reported by `optimism_outputAtBlock`.
+struct L2Output {
+    output_root: B256,
+    l1_origin_number: u64,
+}
+
+async fn fetch_l2_output(
+    endpoints: &EndpointPool,
+    l2_number: u64,
+) -> Result<L2Output, WitnessGenError> {
+    let block_number = format!("0x{:x}", l2_number);
+    fetch_rpc_data(endpoints, Endpoint::L2Node, "optimism_outputAtBlock", vec![block_number.into()])
         .await
-        .expect("L2 Node is not alive");
+        .and_then(|output| {
+            Ok(L2Output {
+                output_root: B256::deserialize(&output["outputRoot"])?,
+                l1_origin_number: json_u64(&output, "/blockRef/l1origin/number")?,
+            })
+        })
+        .map_err(|e| {
+            rpc_check_failed(Endpoint::L2Node, format!("Failed to fetch the L2 output: {}", e))
+        })
+}
 
-    // TODO(Ethan): Check if L1 beacon is alive.
+async fn fetch_l1_block_timestamp(
+    endpoints: &EndpointPool,
+    l1_number: u64,
+) -> Result<u64, WitnessGenError> {
+    let params = vec![json!(format!("0x{:x}", l1_number)), json!(false)];
+    fetch_rpc_data(endpoints, Endpoint::L1, "eth_getBlockByNumber", params)
+        .await
+        .and_then(|block| quantity_u64(&block["timestamp"]))
+        .map_err(|e| rpc_check_failed(Endpoint::L1, format!("L1 Geth is not alive: {}", e)))
+}
+
+/// Checks the L2 block `l2_number` is not beyond the safe head of the L2 node. The batch of a block
+/// beyond the safe head may not be on L1 yet, so its derivation would fail.
+async fn check_l2_block_safe(
+    endpoints: &EndpointPool,
+    l2_hash: B256,
+    l2_number: u64,
+) -> Result<(), WitnessGenError> {
+    let sync_status = fetch_rpc_data(endpoints, Endpoint::L2Node, "optimism_syncStatus", vec![])
+        .await
+        .and_then(|sync_status| Ok(serde_json::from_value::<SyncStatus>(sync_status)?))
+        .map_err(|e| {
+            rpc_check_failed(Endpoint::L2Node, format!("Failed to fetch sync status: {}", e))
+        })?;
+    if l2_number > sync_status.safe_l2.number {
+        return Err(WitnessGenError::not_yet_safe(format!(
+            "L2 block {} is beyond the safe head {}",
+            l2_number, sync_status.safe_l2.number
+        ))
+        .with_params(json!({
+            "l2_hash": l2_hash,
+            "l2_number": l2_number,
+            "safe_head": sync_status.safe_l2,
+            "finalized_head": sync_status.finalized_l2,
+        })));
+    }
 
     Ok(())
 }
 
+// The number of L1 blocks after the L1 origin within which the batch of an L2 block is on L1.
+fn derivation_window(rollup_config: &RollupConfig) -> u64 {
+    rollup_config.seq_window_size + rollup_config.channel_timeout
+}
+
+/// The blocks of a task resolved by the preflight checks.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PreflightReport {
+    pub start_l2_number: u64,
+    pub l2_number: u64,
+    pub l1_head_number: u64,
+    /// The L1 origin of the last L2 block.
+    pub l1_origin_number: u64,
+    /// The number of L1 blocks from the L1 origin to the L1 head.
+    pub l1_distance: u64,
+}
+
+/// Checks the range from `start_l2_number` to `l2_number` is valid and covers at most
+/// `max_range_blocks` blocks, and returns the number of the blocks.
+pub fn check_range(
+    start_l2_number: u64,
+    l2_number: u64,
+    max_range_blocks: u64,
+) -> Result<u64, WitnessGenError> {
+    if start_l2_number > l2_number {
+        return Err(WitnessGenError::invalid_input_hash(format!(
+            "Invalid range: start block {} is after end block {}",
+            start_l2_number, l2_number
+        )));
+    }
+    let num_blocks = l2_number - start_l2_number + 1;
+    if num_blocks > max_range_blocks {
+        return Err(WitnessGenError::invalid_input_hash(format!(
+            "Range of {} blocks exceeds the maximum of {}",
+            num_blocks, max_range_blocks
+        )));
+    }
+    Ok(num_blocks)
+}
+
+/// Checks the task can be derived before its job is accepted: the range covers at most
+/// `max_range_blocks` blocks, the L2 blocks exist and are safe, and the L1 head is canonical and
+/// within the derivation window of `rollup_config` from the L1 origin of the last L2 block.
+pub async fn preflight(
+    chain: &Chain,
+    task: &TaskInfo,
+    rollup_config: &RollupConfig,
+    max_range_blocks: u64,
+) -> Result<PreflightReport, WitnessGenError> {
+    let endpoints = &chain.endpoints;
+    let l2_number = fetch_l2_block_number(endpoints, task.l2_hash).await?;
+    let start_l2_number = if task.is_range() {
+        fetch_l2_block_number(endpoints, task.start_l2_hash).await?
+    } else {
+        l2_number
+    };
+    check_range(start_l2_number, l2_number, max_range_blocks)?;
+    check_l2_block_safe(endpoints, task.l2_hash, l2_number).await?;
+    // The job executes the L2 blocks on top of the state of the parent of the first one.
+    let agreed_l2_number = start_l2_number.saturating_sub(1);
+    check_within_history(&chain.status, Endpoint::L2, agreed_l2_number)?;
+
+    let l1_head_number = fetch_canonical_l1_number(endpoints, task.l1_head_hash).await?;
+    let l1_origin_number = fetch_l2_output(endpoints, l2_number).await?.l1_origin_number;
+    let params = json!({
+        "l2_number": l2_number,
+        "l1_origin_number": l1_origin_number,
+        "l1_head_number": l1_head_number,
+    });
+    // NOTE: The program assumes the L1 head is at or after the L1 origin. Otherwise,
+    // `check-l1-connectivity` underflows.
+    if l1_head_number < l1_origin_number {
+        return Err(WitnessGenError::l1_head_not_descendant(format!(
+            "L1 head {} is before the L1 origin {} of L2 block {}",
+            l1_head_number, l1_origin_number, l2_number
+        ))
+        .with_params(params));
+    }
+    let l1_distance = l1_head_number - l1_origin_number;
+    let derivation_window = derivation_window(rollup_config);
+    if l1_distance > derivation_window {
+        return Err(WitnessGenError::l1_head_too_far(format!(
+            "L1 head {} is more than {} blocks after the L1 origin {}",
+            l1_head_number, derivation_window, l1_origin_number
+        ))
+        .with_params(params));
+    }
+
+    // The derivation reads the L1 blocks from the L1 origin of the agreed L2 block.
+    let agreed_l1_origin_number =
+        fetch_l2_output(endpoints, agreed_l2_number).await?.l1_origin_number;
+    check_within_history(&chain.status, Endpoint::L1, agreed_l1_origin_number)?;
+
+    Ok(PreflightReport {
+        start_l2_number,
+        l2_number,
+        l1_head_number,
+        l1_origin_number,
+        l1_distance,
+    })
+}
+
+/// An inclusive range of blocks or slots.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Range {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// What a job of a task would involve, estimated without running the native host.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ValidationReport {
+    #[serde(flatten)]
+    pub preflight: PreflightReport,
+    /// The output root of the parent of the first L2 block, which the job starts from.
+    pub agreed_output_root: B256,
+    /// The output root of the last L2 block, which the job proves.
+    pub claimed_output_root: B256,
+    /// The L1 blocks the derivation reads, from the L1 origin of the agreed L2 block to the L1
+    /// head.
+    pub l1_block_range: Range,
+    /// The beacon slots of `l1_block_range`, whose blobs may be fetched. It is `None` if the L1
+    /// beacon has not been checked yet.
+    pub blob_slot_range: Option<Range>,
+    /// If `true`, the L1 beacon still retains the blobs of `blob_slot_range`.
+    pub blobs_retained: Option<bool>,
+}
+
+/// Runs the preflight checks of `task` and estimates the L1 blocks and the blobs its job reads.
+pub async fn validate(
+    chain: &Chain,
+    task: &TaskInfo,
+    rollup_config: &RollupConfig,
+    max_range_blocks: u64,
+) -> Result<ValidationReport, WitnessGenError> {
+    let endpoints = &chain.endpoints;
+    let preflight = preflight(chain, task, rollup_config, max_range_blocks).await?;
+    let agreed_output =
+        fetch_l2_output(endpoints, preflight.start_l2_number.saturating_sub(1)).await?;
+    let claimed_output = fetch_l2_output(endpoints, preflight.l2_number).await?;
+    let l1_block_range =
+        Range { from: agreed_output.l1_origin_number, to: preflight.l1_head_number };
+
+    // NOTE: The slot of an L1 block is derived from its timestamp, since every L1 block
+    // after the merge is proposed at the start of its slot.
+    let (blob_slot_range, blobs_retained) = match chain.status.l1_beacon() {
+        Some(beacon) => {
+            let slot = |timestamp: u64| {
+                timestamp.saturating_sub(beacon.genesis_time) / beacon.seconds_per_slot.max(1)
+            };
+            let blob_slot_range = Range {
+                from: slot(fetch_l1_block_timestamp(endpoints, l1_block_range.from).await?),
+                to: slot(fetch_l1_block_timestamp(endpoints, l1_block_range.to).await?),
+            };
+            let blobs_retained = blob_slot_range.from >= beacon.oldest_blob_slot;
+            (Some(blob_slot_range), Some(blobs_retained))
+        }
+        None => (None, None),
+    };
+
+    Ok(ValidationReport {
+        preflight,
+        agreed_output_root: agreed_output.output_root,
+        claimed_output_root: claimed_output.output_root,
+        l1_block_range,
+        blob_slot_range,
+        blobs_retained,
+    })
+}
+
+/// A field of the rollup config which differs between two sources.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConfigMismatch {
+    /// The path of the field, e.g., `genesis.l1.hash`.
+    pub field: String,
+    pub expected: Value,
+    pub actual: Value,
+}
+
+/// Returns the path of the rollup config of `l2_chain_id` kept in this repository.
+pub fn rollup_config_path(l2_chain_id: u64) -> PathBuf {
+    Path::new(ROLLUP_CONFIGS_DIR).join(l2_chain_id.to_string()).join("rollup.json")
+}
+
+// NOTE: The L2 node pads the hex values, e.g., `overhead` and `scalar` as 32 bytes, and
+// checksums the addresses, so the hex strings are compared without the leading zeros and the case.
+fn normalize_hex(value: &Value) -> Value {
+    match value.as_str().and_then(|s| s.strip_prefix("0x")) {
+        Some(hex) => Value::String(format!("0x{}", hex.trim_start_matches('0').to_lowercase())),
+        None => value.clone(),
+    }
+}
+
+/// Compares the fields of `actual` with the ones of `expected`. The fields missing on either side
+/// are skipped, since each source omits the fields it does not know.
+pub(crate) fn diff_rollup_config(
+    path: &str,
+    expected: &Value,
+    actual: &Value,
+) -> Vec<ConfigMismatch> {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => expected
+            .iter()
+            .filter_map(|(key, expected)| Some((key, expected, actual.get(key)?)))
+            .flat_map(|(key, expected, actual)| {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                diff_rollup_config(&path, expected, actual)
+            })
+            .collect(),
+        _ if normalize_hex(expected) == normalize_hex(actual) => vec![],
+        _ => vec![ConfigMismatch {
+            field: path.to_string(),
+            expected: expected.clone(),
+            actual: actual.clone(),
+        }],
+    }
+}
+
+/// Checks the chain IDs of L1 Geth and L2 Geth match the rollup config of the L2 node, and the
+/// rollup config of the L2 node matches field by field `configs/<l2_chain_id>/rollup.json` built
+/// into the binary if there is one, and `rollup_config`, the one every job is run with.
+pub async fn check_chain_config(
+    endpoints: &EndpointPool,
+    rollup_config: &RollupConfig,
+) -> Result<(), WitnessGenError> {
+    let l1_chain_id = fetch_rpc_data(endpoints, Endpoint::L1, "eth_chainId", vec![])
+        .await
+        .and_then(|chain_id| quantity_u64(&chain_id))
+        .map_err(|e| rpc_check_failed(Endpoint::L1, format!("L1 Geth is not alive: {}", e)))?;
+    let l2_chain_id = fetch_rpc_data(endpoints, Endpoint::L2, "eth_chainId", vec![])
+        .await
+        .and_then(|chain_id| quantity_u64(&chain_id))
+        .map_err(|e| rpc_check_failed(Endpoint::L2, format!("L2 Geth is not alive: {}", e)))?;
+    let node_rollup_config =
+        fetch_rpc_data(endpoints, Endpoint::L2Node, "optimism_rollupConfig", vec![])
+            .await
+            .map_err(|e| {
+                rpc_check_failed(Endpoint::L2Node, format!("Failed to fetch rollup config: {}", e))
+            })?;
+
+    let chain_ids = json!({ "l1_chain_id": l1_chain_id, "l2_chain_id": l2_chain_id });
+    let mismatches = diff_rollup_config("", &node_rollup_config, &chain_ids);
+    if !mismatches.is_empty() {
+        return Err(WitnessGenError::config_mismatch(format!(
+            "The chain IDs of L1 Geth ({}) and L2 Geth ({}) do not match the rollup config of L2 \
+             Node",
+            l1_chain_id, l2_chain_id
+        ))
+        .with_params(json!({ "mismatches": mismatches })));
+    }
+
+    if registry::chain_ids().contains(&l2_chain_id) {
+        let registered = registry::embedded(l2_chain_id)?;
+        let embedded_rollup_config: Value = serde_json::from_str(registered.json).map_err(|e| {
+            WitnessGenError::internal(format!("Failed to parse the embedded rollup config: {}", e))
+        })?;
+        check_node_rollup_config(
+            "embedded rollup config",
+            &embedded_rollup_config,
+            &node_rollup_config,
+        )?;
+    }
+
+    let rollup_config = serde_json::to_value(rollup_config).map_err(|e| {
+        WitnessGenError::internal(format!("Failed to serialize rollup config: {}", e))
+    })?;
+    check_node_rollup_config("rollup config", &rollup_config, &node_rollup_config)
+}
+
+// Fails with every field of the rollup config of L2 Node which differs from `expected`, which is
+// described by `name`.
+fn check_node_rollup_config(
+    name: &str,
+    expected: &Value,
+    node_rollup_config: &Value,
+) -> Result<(), WitnessGenError> {
+    let mismatches = diff_rollup_config("", expected, node_rollup_config);
+    if !mismatches.is_empty() {
+        for mismatch in &mismatches {
+            tracing::error!(
+                "Rollup config mismatch at {}: {} in the {}, {} in L2 Node",
+                mismatch.field,
+                mismatch.expected,
+                name,
+                mismatch.actual
+            );
+        }
+        return Err(WitnessGenError::config_mismatch(format!(
+            "The rollup config of L2 Node does not match the {} at {} field(s)",
+            name,
+            mismatches.len()
+        ))
+        .with_params(json!({ "mismatches": mismatches })));
+    }
+
+    Ok(())
+}
+
+/// Checks every URL of every endpoint, switching each endpoint to a healthy one, and the history
+/// windows. The rollup config is resolved through the endpoints only after it, and checked by
+/// [`check_chain_config`].
+pub async fn assert_if_invalid_rpcs(chain: &Chain) -> Result<(), WitnessGenError> {
+    dotenv::dotenv().ok();
+    check_urls(chain, Endpoint::L1, check_l1).await?;
+    let latest_l2_number = check_urls(chain, Endpoint::L2, check_l2).await?;
+    check_urls(chain, Endpoint::L2Node, |url| check_l2_node(url, latest_l2_number)).await?;
+    let beacon_info =
+        check_urls(chain, Endpoint::L1Beacon, |url| check_l1_beacon(&chain.endpoints, url)).await?;
+    chain.status.set_l1_beacon(beacon_info);
+    check_history_window(chain, Endpoint::L1).await?;
+    check_history_window(chain, Endpoint::L2).await?;
+
+    Ok(())
+}
+
+pub fn native_host_runner_path() -> String {
+    env::var("NATIVE_HOST_RUNNER_PATH").unwrap_or(DEFAULT_NATIVE_HOST_RUNNER_PATH.to_string())
+}
+
+/// The result of a single readiness check.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl<T> From<Result<T, WitnessGenError>> for CheckResult {
+    fn from(result: Result<T, WitnessGenError>) -> Self {
+        match result {
+            Ok(_) => Self { ok: true, error: None },
+            Err(e) => Self { ok: false, error: e.message.or(Some(e.code.kind().to_string())) },
+        }
+    }
+}
+
+/// The readiness of the witness generator with the breakdown of each check.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+/// Probes every URL of every endpoint and records the results. Unlike `assert_if_invalid_rpcs`, it
+/// does not stop at the first failure.
+pub async fn check_endpoints(chain: &Chain) -> BTreeMap<String, CheckResult> {
+    let (l1, l2, l1_beacon) = tokio::join!(
+        check_urls(chain, Endpoint::L1, check_l1),
+        check_urls(chain, Endpoint::L2, check_l2),
+        check_urls(chain, Endpoint::L1Beacon, |url| check_l1_beacon(&chain.endpoints, url))
+    );
+    let l2_node = match &l2 {
+        Ok(latest_l2_number) => {
+            check_urls(chain, Endpoint::L2Node, |url| check_l2_node(url, *latest_l2_number)).await
+        }
+        Err(_) => record_check(
+            &chain.status,
+            Endpoint::L2Node,
+            Err(WitnessGenError::rpc_unavailable("L2 Geth is not available".to_string())),
+        ),
+    };
+    if let Ok(beacon_info) = &l1_beacon {
+        chain.status.set_l1_beacon(beacon_info.clone());
+    }
+
+    let checks = [
+        (Endpoint::L1, CheckResult::from(l1)),
+        (Endpoint::L2, CheckResult::from(l2)),
+        (Endpoint::L2Node, CheckResult::from(l2_node)),
+        (Endpoint::L1Beacon, CheckResult::from(l1_beacon)),
+    ];
+    checks.into_iter().map(|(endpoint, check)| (endpoint.name().to_string(), check)).collect()
+}
+
+/// Reports the readiness of each of `chains` from the last check of each endpoint, recorded at
+/// startup and by the health monitor, and the checks of its store. No endpoint is called, so it is
+/// cheap to call on every request. If more than one chain is served, the checks of each chain are
+/// keyed by `<chain_id>.<check>`.
+pub fn check_readiness(chains: &[ChainHandle]) -> ReadinessReport {
+    let mut checks = BTreeMap::new();
+    for handle in chains {
+        let last_checks = handle.chain.status.last_checks();
+        let mut chain_checks: BTreeMap<String, CheckResult> = Endpoint::ALL
+            .into_iter()
+            .map(|endpoint| {
+                let check = match last_checks.get(endpoint.name()) {
+                    Some(check) => CheckResult { ok: check.ok, error: check.error.clone() },
+                    None => CheckResult { ok: false, error: Some("Not checked yet".to_string()) },
+                };
+                (endpoint.name().to_string(), check)
+            })
+            .collect();
+        let store = handle.witness_db.check_writable().map_err(WitnessGenError::from);
+        chain_checks.insert("store".to_string(), CheckResult::from(store));
+        checks.extend(chain_checks.into_iter().map(|(name, check)| match chains.len() {
+            1 => (name, check),
+            _ => (format!("{}.{}", handle.chain_id(), name), check),
+        }));
+    }
+
+    let native_host_runner_path = native_host_runner_path();
+    let native_host_runner = if Path::new(&native_host_runner_path).is_file() {
+        Ok(())
+    } else {
+        Err(WitnessGenError::internal(format!("{} does not exist", native_host_runner_path)))
+    };
+    checks.insert("native_host_runner".to_string(), CheckResult::from(native_host_runner));
+    let ready = checks.values().all(|check| check.ok);
+
+    ReadinessReport { ready, checks }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::process::Command;
-
+    use serde_json::json;
     use tokio::runtime::Runtime;
 
-    use super::{assert_if_invalid_rpcs, check_rollup_config_before_mpt_time};
+    use super::{
+        assert_if_invalid_rpcs, check_chain_config, check_node_rollup_config, check_range,
+        diff_rollup_config, fetch_beacon_data_at, fetch_rpc_data_at, json_u64, quantity_u64,
+    };
+    use crate::{chain::Chain, rollup_config::ResolvedRollupConfig};
 
     #[test]
-    fn test_online_rpc_valid() {
+    fn test_parse_integers() {
+        let head = json!({ "data": { "header": { "message": { "slot": "10512345" } } } });
+        assert_eq!(json_u64(&head, "/data/header/message/slot").unwrap(), 10512345);
+        assert!(json_u64(&head, "/data/header/slot").is_err());
+        assert_eq!(json_u64(&json!({ "chain_id": 17000 }), "/chain_id").unwrap(), 17000);
+        assert_eq!(quantity_u64(&json!("0x4268")).unwrap(), 17000);
+        assert!(quantity_u64(&json!(17000)).is_err());
+    }
+
+    #[test]
+    fn test_check_range() {
+        assert_eq!(check_range(100, 100, 1).unwrap(), 1);
+        assert_eq!(check_range(100, 109, 10).unwrap(), 10);
+        let err = check_range(100, 110, 10).unwrap_err();
+        assert_eq!(err.message.as_deref(), Some("Range of 11 blocks exceeds the maximum of 10"));
+        assert!(check_range(101, 100, 10).is_err());
+    }
+
+    #[test]
+    fn test_errors_without_url() {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
-            assert_if_invalid_rpcs().await.unwrap();
+            let url = "http://127.0.0.1:1/v2/secret-api-key";
+            let err = fetch_rpc_data_at(url, "net_version", vec![]).await.unwrap_err();
+            assert!(!err.to_string().contains("secret-api-key"), "{}", err);
+            let err = fetch_beacon_data_at(url, "eth/v1/beacon/genesis").await.unwrap_err();
+            assert!(!err.to_string().contains("secret-api-key"), "{}", err);
         });
     }
 
     #[test]
-    fn test_online_rollup_config() {
-        let _ = Command::new("cp")
-            .args(&["-r", "../configs", "."])
-            .output()
-            .expect("Failed to copy .env file");
+    fn test_diff_rollup_config() {
+        let file = json!({
+            "genesis": {
+                "l1": { "number": 18067255, "hash": "0xe459c500b760ed52" },
+                "system_config": { "overhead": "0xbc", "baseFeeScalar": null }
+            },
+            "l1_chain_id": 1,
+            "batch_inbox_address": "0xff00000000000000000000000000000000000255",
+            "canyon_base_fee_params": { "max_change_denominator": "0xfa" }
+        });
+        let node = json!({
+            "genesis": {
+                "l1": { "number": 18067255, "hash": "0xE459C500B760ED52" },
+                "system_config": { "overhead": "0x00000000000000bc", "baseFeeScalar": 0 }
+            },
+            "l1_chain_id": 11155111,
+            "batch_inbox_address": "0xFF00000000000000000000000000000000000255"
+        });
+
+        let mismatches = diff_rollup_config("", &file, &node);
+        let fields: Vec<&str> = mismatches.iter().map(|m| m.field.as_str()).collect();
+        assert_eq!(fields, vec!["genesis.system_config.baseFeeScalar", "l1_chain_id"]);
+        assert_eq!(mismatches[1].expected, json!(1));
+        assert_eq!(mismatches[1].actual, json!(11155111));
+    }
+
+    #[test]
+    fn test_check_node_rollup_config() {
+        let node = json!({ "l2_chain_id": 255, "block_time": 2 });
+        assert!(check_node_rollup_config("rollup config", &json!({ "l2_chain_id": 255 }), &node)
+            .is_ok());
+        let expected = json!({ "l2_chain_id": 255, "block_time": 1 });
+        let err = check_node_rollup_config("embedded rollup config", &expected, &node).unwrap_err();
+        assert_eq!(
+            err.message.as_deref(),
+            Some(
+                "The rollup config of L2 Node does not match the embedded rollup config at 1 \
+                 field(s)"
+            )
+        );
+    }
 
+    #[test]
+    fn test_online_rpc_valid() {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
-            check_rollup_config_before_mpt_time().await.unwrap();
+            let chain = Chain::from_env();
+            assert_if_invalid_rpcs(&chain).await.unwrap();
+            let rollup_config =
+                ResolvedRollupConfig::resolve(&chain.endpoints, None).await.unwrap();
+            check_chain_config(&chain.endpoints, &rollup_config.config).await.unwrap();
         });
-
-        let _ = Command::new("rm")
-            .args(&["-rf", "./configs"])
-            .output()
-            .expect("Failed to copy .env file");
     }
 }
@@ -0,0 +1,475 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checker::{Endpoint, DEFAULT_RPC_REQUEST_TIMEOUT_SECS},
+    endpoints::redact,
+    executor::{DEFAULT_JOB_COOLDOWN_MS, DEFAULT_MAX_RANGE_BLOCKS, DEFAULT_QUEUE_CAPACITY},
+    interface::{
+        DEFAULT_MAX_REQUEST_BODY_BYTES, DEFAULT_SERVER_THREADS, DEFAULT_WITNESSGEN_RPC_ENDPOINT,
+        DEFAULT_WITNESS_STORE_PATH,
+    },
+    monitor::DEFAULT_HEALTH_CHECK_INTERVAL_SECS,
+    rollup_config::RollupConfigSource,
+    witness_db::{DEFAULT_CAPACITY, DEFAULT_VALUE_EXPIRING_SECS},
+};
+
+/// The configuration of the witness generator, read from a TOML file such as `witnessgen.toml`.
+/// A missing section or field takes its default.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WitnessGenConfig {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub workers: WorkerConfig,
+    pub timeouts: TimeoutConfig,
+    pub rpc: EndpointsConfig,
+    pub policies: PolicyConfig,
+    /// The chains served by the process. If it is empty, the single chain of `rpc` is served.
+    pub chains: Vec<ChainConfig>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// The address the JSON-RPC server listens on.
+    pub endpoint: String,
+    /// The number of threads of the JSON-RPC server.
+    pub threads: usize,
+    pub max_request_body_bytes: usize,
+    /// The JSON file of API keys. If it is not given, every call is allowed.
+    pub api_keys: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: DEFAULT_WITNESSGEN_RPC_ENDPOINT.to_string(),
+            threads: DEFAULT_SERVER_THREADS,
+            max_request_body_bytes: DEFAULT_MAX_REQUEST_BODY_BYTES,
+            api_keys: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub data_path: String,
+    /// The maximum number of witnesses kept by the store.
+    pub capacity: usize,
+    /// How long a witness is kept in seconds.
+    pub value_expiring_secs: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            data_path: DEFAULT_WITNESS_STORE_PATH.to_string(),
+            capacity: DEFAULT_CAPACITY,
+            value_expiring_secs: DEFAULT_VALUE_EXPIRING_SECS,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkerConfig {
+    /// The number of tasks which can wait for the executor.
+    pub queue_capacity: usize,
+    /// How long the executor waits after a job before picking up the next one in milliseconds.
+    pub cooldown_ms: u64,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self { queue_capacity: DEFAULT_QUEUE_CAPACITY, cooldown_ms: DEFAULT_JOB_COOLDOWN_MS }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutConfig {
+    /// The interval to check the endpoints in the background in seconds.
+    pub health_check_interval_secs: u64,
+    /// The timeout of a request of the witness generator to an endpoint in seconds. The requests
+    /// of the data fetcher and the native host are not affected.
+    pub rpc_request_secs: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            health_check_interval_secs: DEFAULT_HEALTH_CHECK_INTERVAL_SECS,
+            rpc_request_secs: DEFAULT_RPC_REQUEST_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// The URLs of each endpoint, the primary one first and then its fallbacks.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EndpointsConfig {
+    pub l1: Vec<String>,
+    pub l1_beacon: Vec<String>,
+    pub l2: Vec<String>,
+    pub l2_node: Vec<String>,
+}
+
+impl EndpointsConfig {
+    pub fn urls(&self, endpoint: Endpoint) -> &[String] {
+        match endpoint {
+            Endpoint::L1 => &self.l1,
+            Endpoint::L1Beacon => &self.l1_beacon,
+            Endpoint::L2 => &self.l2,
+            Endpoint::L2Node => &self.l2_node,
+        }
+    }
+
+    fn urls_mut(&mut self, endpoint: Endpoint) -> &mut Vec<String> {
+        match endpoint {
+            Endpoint::L1 => &mut self.l1,
+            Endpoint::L1Beacon => &mut self.l1_beacon,
+            Endpoint::L2 => &mut self.l2,
+            Endpoint::L2Node => &mut self.l2_node,
+        }
+    }
+
+    /// Overrides the primary URL of each endpoint by its env key, e.g., `L1_RPC`, and the fallbacks
+    /// by `<env key>_FALLBACKS`.
+    pub fn merge_env(&mut self) {
+        for endpoint in Endpoint::ALL {
+            let urls = self.urls_mut(endpoint);
+            if let Ok(primary) = env::var(endpoint.env_key()) {
+                match urls.first_mut() {
+                    Some(url) => *url = primary,
+                    None => urls.push(primary),
+                }
+            }
+            if let Ok(fallbacks) = env::var(format!("{}_FALLBACKS", endpoint.env_key())) {
+                urls.truncate(1);
+                urls.extend(
+                    fallbacks
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|url| !url.is_empty())
+                        .map(Into::into),
+                );
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// The maximum number of L2 blocks a range witness can cover.
+    pub max_range_blocks: u64,
+    /// If `true`, a witness is marked completed without running it in the SP1 executor. It defaults
+    /// to `true`. See `execute_witness`.
+    pub skip_simulation: bool,
+    /// The maximum number of cycles of a witness. A witness exceeding it is marked failed.
+    pub max_cycles: Option<u64>,
+    /// Where the rollup config is read from: `rpc`, `embedded` or the path of a JSON file. If it
+    /// is not given, it is detected at startup.
+    pub rollup_config: Option<RollupConfigSource>,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            max_range_blocks: DEFAULT_MAX_RANGE_BLOCKS,
+            skip_simulation: true,
+            max_cycles: None,
+            rollup_config: None,
+        }
+    }
+}
+
+/// A chain served by the process. Its fields left out take the top-level ones.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChainConfig {
+    /// The L2 chain ID, which must be the one of L2 Geth. It is required if `chains` is set.
+    pub chain_id: Option<u64>,
+    /// The URLs of the endpoints of the chain. An endpoint left empty takes the one of `rpc`, e.g.,
+    /// L1 shared by the chains.
+    pub rpc: EndpointsConfig,
+    /// Where the rollup config of the chain is read from. It defaults to `policies.rollup_config`.
+    pub rollup_config: Option<RollupConfigSource>,
+    /// The store of the chain. It defaults to `<chain_id>/<file name>` in the directory of
+    /// `storage.data_path`, e.g., `data/255/witness_store`. The audit log and the witness metadata
+    /// are kept in its directory, so it must not be shared with another chain.
+    pub data_path: Option<String>,
+}
+
+impl WitnessGenConfig {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let config = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&config).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    /// Returns the chains to serve with their fields left out filled by the top-level ones.
+    pub fn profiles(&self) -> Vec<ChainConfig> {
+        if self.chains.is_empty() {
+            return vec![ChainConfig {
+                chain_id: None,
+                rpc: self.rpc.clone(),
+                rollup_config: self.policies.rollup_config.clone(),
+                data_path: Some(self.storage.data_path.clone()),
+            }];
+        }
+        self.chains
+            .iter()
+            .map(|chain| {
+                let mut rpc = chain.rpc.clone();
+                for endpoint in Endpoint::ALL {
+                    if rpc.urls(endpoint).is_empty() {
+                        *rpc.urls_mut(endpoint) = self.rpc.urls(endpoint).to_vec();
+                    }
+                }
+                let data_path = chain
+                    .data_path
+                    .clone()
+                    .or_else(|| chain.chain_id.map(|chain_id| self.chain_data_path(chain_id)));
+                ChainConfig {
+                    chain_id: chain.chain_id,
+                    rpc,
+                    rollup_config: chain
+                        .rollup_config
+                        .clone()
+                        .or_else(|| self.policies.rollup_config.clone()),
+                    data_path,
+                }
+            })
+            .collect()
+    }
+
+    fn chain_data_path(&self, chain_id: u64) -> String {
+        let path = Path::new(&self.storage.data_path);
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let file_name = path.file_name().map(PathBuf::from).unwrap_or_default();
+        dir.join(chain_id.to_string()).join(file_name).display().to_string()
+    }
+
+    /// Checks every field and reports all the invalid ones at once.
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        if self.server.endpoint.parse::<SocketAddr>().is_err() {
+            errors.push(format!(
+                "server.endpoint `{}` is not a socket address",
+                self.server.endpoint
+            ));
+        }
+        let positives = [
+            ("server.threads", self.server.threads as u64),
+            ("server.max_request_body_bytes", self.server.max_request_body_bytes as u64),
+            ("storage.capacity", self.storage.capacity as u64),
+            ("storage.value_expiring_secs", self.storage.value_expiring_secs as u64),
+            ("workers.queue_capacity", self.workers.queue_capacity as u64),
+            ("timeouts.health_check_interval_secs", self.timeouts.health_check_interval_secs),
+            ("timeouts.rpc_request_secs", self.timeouts.rpc_request_secs),
+            ("policies.max_range_blocks", self.policies.max_range_blocks),
+        ];
+        for (field, value) in positives {
+            if value == 0 {
+                errors.push(format!("{} must be positive", field));
+            }
+        }
+        if self.policies.skip_simulation && self.policies.max_cycles.is_some() {
+            errors.push("policies.max_cycles cannot be used with skip_simulation".to_string());
+        }
+        let mut chain_ids = BTreeSet::new();
+        let mut data_dirs = BTreeSet::new();
+        for (i, profile) in self.profiles().iter().enumerate() {
+            let prefix = if self.chains.is_empty() {
+                "rpc".to_string()
+            } else {
+                match profile.chain_id {
+                    Some(chain_id) if !chain_ids.insert(chain_id) => {
+                        errors.push(format!("chains[{}].chain_id {} is duplicated", i, chain_id))
+                    }
+                    Some(_) => {}
+                    None => errors.push(format!("chains[{}].chain_id is not set", i)),
+                }
+                if let Some(data_path) = &profile.data_path {
+                    let data_dir = Path::new(data_path).parent().map(Path::to_path_buf);
+                    if !data_dirs.insert(data_dir.unwrap_or_default()) {
+                        errors.push(format!(
+                            "chains[{}].data_path `{}` is in the directory of the store of another \
+                             chain",
+                            i, data_path
+                        ));
+                    }
+                }
+                format!("chains[{}].rpc", i)
+            };
+            for endpoint in Endpoint::ALL {
+                let urls = profile.rpc.urls(endpoint);
+                if urls.is_empty() {
+                    errors.push(format!(
+                        "{}.{} (or {}) is not set",
+                        prefix,
+                        endpoint.name(),
+                        endpoint.env_key()
+                    ));
+                }
+                for url in urls.iter().filter(|url| reqwest::Url::parse(url).is_err()) {
+                    errors.push(format!(
+                        "{}.{} has an invalid URL `{}`",
+                        prefix,
+                        endpoint.name(),
+                        url
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid config:\n  {}", errors.join("\n  ")))
+        }
+    }
+
+    /// Returns the config in TOML with only the scheme, host and port of the RPC URLs, which often
+    /// carry an API key.
+    pub fn to_redacted_toml(&self) -> String {
+        let mut config = self.clone();
+        for rpc in
+            std::iter::once(&mut config.rpc).chain(config.chains.iter_mut().map(|c| &mut c.rpc))
+        {
+            for endpoint in Endpoint::ALL {
+                rpc.urls_mut(endpoint).iter_mut().for_each(|url| *url = redact(url));
+            }
+        }
+        toml::to_string(&config).unwrap_or_else(|e| format!("<{}>", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: WitnessGenConfig = toml::from_str(
+            r#"
+            [server]
+            endpoint = "127.0.0.1:3030"
+
+            [storage]
+            capacity = 20
+
+            [rpc]
+            l1 = ["http://l1-primary:8545", "https://l1-fallback/v1/secret"]
+
+            [policies]
+            max_range_blocks = 5
+            rollup_config = "embedded"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.server.endpoint, "127.0.0.1:3030");
+        assert_eq!(config.server.threads, DEFAULT_SERVER_THREADS);
+        assert_eq!(config.storage.capacity, 20);
+        assert_eq!(config.storage.data_path, DEFAULT_WITNESS_STORE_PATH);
+        assert_eq!(config.workers, WorkerConfig::default());
+        assert_eq!(config.rpc.l1.len(), 2);
+        assert_eq!(config.policies.max_range_blocks, 5);
+        assert!(config.policies.skip_simulation);
+        assert_eq!(config.policies.rollup_config, Some(RollupConfigSource::Embedded));
+
+        let redacted = config.to_redacted_toml();
+        assert!(redacted.contains("https://l1-fallback"));
+        assert!(!redacted.contains("secret"));
+
+        assert!(toml::from_str::<WitnessGenConfig>("[server]\nport = 3030").is_err());
+        let example = include_str!("../../witnessgen.example.toml");
+        assert!(toml::from_str::<WitnessGenConfig>(example).is_ok());
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let mut config = WitnessGenConfig {
+            rpc: EndpointsConfig {
+                l1: vec!["http://l1:8545".to_string()],
+                l1_beacon: vec!["http://l1-beacon:5052".to_string()],
+                l2: vec!["http://l2:8545".to_string()],
+                l2_node: vec!["http://l2-node:7545".to_string()],
+            },
+            ..Default::default()
+        };
+        config.validate().unwrap();
+
+        config.server.endpoint = "localhost".to_string();
+        config.workers.queue_capacity = 0;
+        config.rpc.l2_node = vec![];
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("server.endpoint"));
+        assert!(message.contains("workers.queue_capacity"));
+        assert!(message.contains("rpc.l2_node (or L2_NODE_RPC) is not set"));
+    }
+
+    #[test]
+    fn test_chain_profiles() {
+        let config: WitnessGenConfig = toml::from_str(
+            r#"
+            [storage]
+            data_path = "data/witness_store"
+
+            [rpc]
+            l1 = ["http://l1:8545"]
+            l1_beacon = ["http://l1-beacon:5052"]
+
+            [policies]
+            rollup_config = "embedded"
+
+            [[chains]]
+            chain_id = 255
+            rpc = { l2 = ["http://l2-255:8545"], l2_node = ["http://l2-node-255:7545"] }
+
+            [[chains]]
+            chain_id = 2358
+            rollup_config = "rpc"
+            data_path = "/var/lib/witnessgen/2358"
+            rpc = { l2 = ["http://l2-2358:8545"], l2_node = ["http://l2-node-2358:7545"] }
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let profiles = config.profiles();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].rpc.l1, config.rpc.l1);
+        assert_eq!(profiles[0].rpc.l2, vec!["http://l2-255:8545".to_string()]);
+        assert_eq!(profiles[0].rollup_config, Some(RollupConfigSource::Embedded));
+        assert_eq!(profiles[0].data_path.as_deref(), Some("data/255/witness_store"));
+        assert_eq!(profiles[1].rollup_config, Some(RollupConfigSource::Rpc));
+        assert_eq!(profiles[1].data_path.as_deref(), Some("/var/lib/witnessgen/2358"));
+
+        let mut invalid = config.clone();
+        invalid.chains[1].chain_id = Some(255);
+        invalid.chains.push(ChainConfig::default());
+        let message = invalid.validate().unwrap_err().to_string();
+        assert!(message.contains("chains[1].chain_id 255 is duplicated"));
+        assert!(message.contains("chains[2].chain_id is not set"));
+        assert!(message.contains("chains[2].rpc.l2 (or L2_RPC) is not set"));
+
+        // The stores of the chains would share the audit log and the witness metadata.
+        let mut invalid = config.clone();
+        invalid.chains[1].data_path = Some("data/255/witness_store_2358".to_string());
+        let message = invalid.validate().unwrap_err().to_string();
+        assert!(message.contains(
+            "chains[1].data_path `data/255/witness_store_2358` is in the directory of the store of \
+             another chain"
+        ));
+    }
+}
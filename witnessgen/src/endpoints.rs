@@ -0,0 +1,173 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, sync::RwLock};
+
+use crate::{checker::Endpoint, metrics::METRICS};
+
+/// The URLs of each endpoint, the primary one first. An endpoint is served by its first healthy URL,
+/// so it fails over to the next one while the preferred ones are unhealthy and fails back once they
+/// recover.
+pub struct EndpointPool {
+    pools: RwLock<BTreeMap<Endpoint, UrlPool>>,
+}
+
+struct UrlPool {
+    urls: Vec<String>,
+    healthy: Vec<bool>,
+    active: usize,
+}
+
+impl EndpointPool {
+    pub fn new(urls: BTreeMap<Endpoint, Vec<String>>) -> Self {
+        let pools = urls
+            .into_iter()
+            .map(|(endpoint, urls)| {
+                let healthy = vec![true; urls.len()];
+                (endpoint, UrlPool { urls, healthy, active: 0 })
+            })
+            .collect();
+        Self { pools: RwLock::new(pools) }
+    }
+
+    /// Reads the primary URL of each endpoint from its env key, e.g., `L1_RPC`, and the fallbacks
+    /// from the comma-separated list of `<env key>_FALLBACKS`, e.g., `L1_RPC_FALLBACKS`.
+    pub fn from_env() -> Self {
+        dotenv::dotenv().ok();
+        let urls = Endpoint::ALL
+            .into_iter()
+            .map(|endpoint| {
+                let fallbacks = env::var(format!("{}_FALLBACKS", endpoint.env_key()));
+                let urls = env::var(endpoint.env_key())
+                    .into_iter()
+                    .chain(
+                        fallbacks
+                            .iter()
+                            .flat_map(|fallbacks| fallbacks.split(','))
+                            .map(|url| url.trim().to_string()),
+                    )
+                    .filter(|url| !url.is_empty())
+                    .collect();
+                (endpoint, urls)
+            })
+            .collect();
+        Self::new(urls)
+    }
+
+    /// Returns the URLs of `endpoint`, the primary one first.
+    pub fn urls(&self, endpoint: Endpoint) -> Vec<String> {
+        let pools = self.pools.read().unwrap();
+        pools.get(&endpoint).map(|pool| pool.urls.clone()).unwrap_or_default()
+    }
+
+    /// Returns the URL `endpoint` is served by.
+    pub fn active(&self, endpoint: Endpoint) -> Option<String> {
+        let pools = self.pools.read().unwrap();
+        pools.get(&endpoint).and_then(|pool| pool.urls.get(pool.active).cloned())
+    }
+
+    /// Returns the URL `endpoint` is served by, or an error if it has no URL.
+    pub fn url(&self, endpoint: Endpoint) -> anyhow::Result<String> {
+        self.active(endpoint).ok_or_else(|| anyhow::anyhow!("{} is not set", endpoint.env_key()))
+    }
+
+    /// Records the health of each URL of `endpoint` in the order of `urls` and switches to the first
+    /// healthy one. If none is healthy, the active URL is kept.
+    pub fn update(&self, endpoint: Endpoint, healthy: Vec<bool>) {
+        let mut pools = self.pools.write().unwrap();
+        let Some(pool) = pools.get_mut(&endpoint) else {
+            return;
+        };
+        if healthy.len() != pool.urls.len() {
+            return;
+        }
+        pool.healthy = healthy;
+        if let Some(active) = pool.healthy.iter().position(|healthy| *healthy) {
+            if active != pool.active {
+                tracing::warn!(
+                    "{} switches from {} to {}",
+                    endpoint.name(),
+                    redact(&pool.urls[pool.active]),
+                    redact(&pool.urls[active])
+                );
+                METRICS.endpoint_failovers.with_label_values(&[endpoint.name()]).inc();
+                pool.active = active;
+            }
+        }
+    }
+
+    /// Returns the state of the URLs of each endpoint, keyed by the name of the endpoint.
+    pub fn report(&self) -> BTreeMap<String, Vec<UrlStatus>> {
+        let pools = self.pools.read().unwrap();
+        pools
+            .iter()
+            .map(|(endpoint, pool)| {
+                let urls = pool
+                    .urls
+                    .iter()
+                    .zip(&pool.healthy)
+                    .enumerate()
+                    .map(|(i, (url, healthy))| UrlStatus {
+                        url: redact(url),
+                        healthy: *healthy,
+                        active: i == pool.active,
+                    })
+                    .collect();
+                (endpoint.name().to_string(), urls)
+            })
+            .collect()
+    }
+}
+
+/// The state of a URL of an endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UrlStatus {
+    /// The scheme, host and port of the URL. The path and the query are left out since they often
+    /// carry an API key.
+    pub url: String,
+    /// If `true`, the last check of the URL has passed.
+    pub healthy: bool,
+    /// If `true`, the endpoint is served by this URL.
+    pub active: bool,
+}
+
+/// Leaves only the scheme, host and port of `url`.
+pub(crate) fn redact(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => match url.port() {
+            Some(port) => format!("{}://{}:{}", url.scheme(), url.host_str().unwrap_or(""), port),
+            None => format!("{}://{}", url.scheme(), url.host_str().unwrap_or("")),
+        },
+        Err(_) => "<invalid url>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failover() {
+        let pool = EndpointPool::new(BTreeMap::from([(
+            Endpoint::L1,
+            vec!["http://primary:8545".to_string(), "https://fallback/v1/secret".to_string()],
+        )]));
+        assert_eq!(pool.active(Endpoint::L1).as_deref(), Some("http://primary:8545"));
+        assert_eq!(pool.active(Endpoint::L2), None);
+
+        pool.update(Endpoint::L1, vec![false, true]);
+        assert_eq!(pool.active(Endpoint::L1).as_deref(), Some("https://fallback/v1/secret"));
+        assert_eq!(
+            pool.report()["l1"],
+            vec![
+                UrlStatus { url: "http://primary:8545".to_string(), healthy: false, active: false },
+                UrlStatus { url: "https://fallback".to_string(), healthy: true, active: true },
+            ]
+        );
+
+        // The active URL is kept while none is healthy, and it fails back to the primary one.
+        pool.update(Endpoint::L1, vec![false, false]);
+        assert_eq!(pool.active(Endpoint::L1).as_deref(), Some("https://fallback/v1/secret"));
+        pool.update(Endpoint::L1, vec![true, true]);
+        assert_eq!(pool.active(Endpoint::L1).as_deref(), Some("http://primary:8545"));
+    }
+}
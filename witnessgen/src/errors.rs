@@ -1,12 +1,33 @@
 use jsonrpc_http_server::jsonrpc_core::{Error as JsonError, ErrorCode as JsonErrorCode};
 use serde::de::{Deserialize, Deserializer};
 use serde::ser::{Serialize, Serializer};
+use serde_json::Value;
 
 /// Error Code
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ErrorCode {
     InvalidInputHash,
     AlreadyInProgress,
+    L2BlockNotFound,
+    L1HeadNotFound,
+    L1HeadNotDescendant,
+    BlobsUnavailable,
+    RpcUnavailable,
+    QueueFull,
+    Timeout,
+    Cancelled,
+    Internal,
+    Unauthorized,
+    ExecutionFailed,
+    ExceedsCycleBudget,
+    ConfigMismatch,
+    Degraded,
+    NotYetSafe,
+    L1HeadTooFar,
+    BeyondHistoryWindow,
+    UnknownChain,
+    /// A code which is not known to this version of the witness generator.
+    Unknown(i64),
 }
 
 impl ErrorCode {
@@ -14,6 +35,52 @@ impl ErrorCode {
         match *self {
             ErrorCode::InvalidInputHash => 1000,
             ErrorCode::AlreadyInProgress => 1001,
+            ErrorCode::L2BlockNotFound => 1002,
+            ErrorCode::L1HeadNotFound => 1003,
+            ErrorCode::L1HeadNotDescendant => 1004,
+            ErrorCode::BlobsUnavailable => 1005,
+            ErrorCode::RpcUnavailable => 1006,
+            ErrorCode::QueueFull => 1007,
+            ErrorCode::Timeout => 1008,
+            ErrorCode::Cancelled => 1009,
+            ErrorCode::Internal => 1010,
+            ErrorCode::Unauthorized => 1011,
+            ErrorCode::ExecutionFailed => 1012,
+            ErrorCode::ExceedsCycleBudget => 1013,
+            ErrorCode::ConfigMismatch => 1014,
+            ErrorCode::Degraded => 1015,
+            ErrorCode::NotYetSafe => 1016,
+            ErrorCode::L1HeadTooFar => 1017,
+            ErrorCode::BeyondHistoryWindow => 1018,
+            ErrorCode::UnknownChain => 1019,
+            ErrorCode::Unknown(code) => code,
+        }
+    }
+
+    /// A machine-readable name of the error, carried in the `kind` field of the error data.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            ErrorCode::InvalidInputHash => "InvalidInputHash",
+            ErrorCode::AlreadyInProgress => "AlreadyInProgress",
+            ErrorCode::L2BlockNotFound => "L2BlockNotFound",
+            ErrorCode::L1HeadNotFound => "L1HeadNotFound",
+            ErrorCode::L1HeadNotDescendant => "L1HeadNotDescendant",
+            ErrorCode::BlobsUnavailable => "BlobsUnavailable",
+            ErrorCode::RpcUnavailable => "RpcUnavailable",
+            ErrorCode::QueueFull => "QueueFull",
+            ErrorCode::Timeout => "Timeout",
+            ErrorCode::Cancelled => "Cancelled",
+            ErrorCode::Internal => "Internal",
+            ErrorCode::Unauthorized => "Unauthorized",
+            ErrorCode::ExecutionFailed => "ExecutionFailed",
+            ErrorCode::ExceedsCycleBudget => "ExceedsCycleBudget",
+            ErrorCode::ConfigMismatch => "ConfigMismatch",
+            ErrorCode::Degraded => "Degraded",
+            ErrorCode::NotYetSafe => "NotYetSafe",
+            ErrorCode::L1HeadTooFar => "L1HeadTooFar",
+            ErrorCode::BeyondHistoryWindow => "BeyondHistoryWindow",
+            ErrorCode::UnknownChain => "UnknownChain",
+            ErrorCode::Unknown(_) => "Unknown",
         }
     }
 }
@@ -23,7 +90,25 @@ impl From<i64> for ErrorCode {
         match code {
             1000 => ErrorCode::InvalidInputHash,
             1001 => ErrorCode::AlreadyInProgress,
-            _ => panic!("not supported code: {:?}", code),
+            1002 => ErrorCode::L2BlockNotFound,
+            1003 => ErrorCode::L1HeadNotFound,
+            1004 => ErrorCode::L1HeadNotDescendant,
+            1005 => ErrorCode::BlobsUnavailable,
+            1006 => ErrorCode::RpcUnavailable,
+            1007 => ErrorCode::QueueFull,
+            1008 => ErrorCode::Timeout,
+            1009 => ErrorCode::Cancelled,
+            1010 => ErrorCode::Internal,
+            1011 => ErrorCode::Unauthorized,
+            1012 => ErrorCode::ExecutionFailed,
+            1013 => ErrorCode::ExceedsCycleBudget,
+            1014 => ErrorCode::ConfigMismatch,
+            1015 => ErrorCode::Degraded,
+            1016 => ErrorCode::NotYetSafe,
+            1017 => ErrorCode::L1HeadTooFar,
+            1018 => ErrorCode::BeyondHistoryWindow,
+            1019 => ErrorCode::UnknownChain,
+            _ => ErrorCode::Unknown(code),
         }
     }
 }
@@ -47,6 +132,34 @@ impl Serialize for ErrorCode {
     }
 }
 
+/// The `data` field of a JSON-RPC error returned by the witness generator.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct ErrorData {
+    /// The name of the `ErrorCode`.
+    pub kind: String,
+    /// The parameters which caused the error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    /// The number of seconds the client should wait before retrying.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+/// The error of a failed job, which is stored with its witness.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct JobError {
+    pub code: ErrorCode,
+    pub message: Option<String>,
+    #[serde(flatten)]
+    pub data: ErrorData,
+}
+
+impl From<&WitnessGenError> for JobError {
+    fn from(err: &WitnessGenError) -> Self {
+        Self { code: err.code, message: err.message.clone(), data: err.data() }
+    }
+}
+
 /// Error object as defined in Spec
 #[derive(Debug)]
 pub struct WitnessGenError {
@@ -54,6 +167,10 @@ pub struct WitnessGenError {
     pub code: ErrorCode,
     /// Message
     pub message: Option<String>,
+    /// Parameters which caused the error
+    pub params: Option<Value>,
+    /// Retry-after hint in seconds
+    pub retry_after_secs: Option<u64>,
 }
 
 impl std::error::Error for WitnessGenError {}
@@ -66,13 +183,58 @@ impl std::fmt::Display for WitnessGenError {
 
 impl From<&WitnessGenError> for JsonError {
     fn from(err: &WitnessGenError) -> Self {
-        Self { code: JsonErrorCode::InternalError, message: err.to_string(), data: None }
+        Self {
+            code: JsonErrorCode::ServerError(err.code.code()),
+            message: err.message.clone().unwrap_or_else(|| err.code.kind().to_string()),
+            data: serde_json::to_value(err.data()).ok(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for WitnessGenError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::internal(err.to_string())
     }
 }
 
 impl WitnessGenError {
+    /// The default retry-after hint for a request rejected because another one is in progress.
+    pub const IN_PROGRESS_RETRY_AFTER_SECS: u64 = 30;
+    /// The default retry-after hint for a request rejected while the witness generator is degraded,
+    /// which is the default interval of the health monitor.
+    pub const DEGRADED_RETRY_AFTER_SECS: u64 = 30;
+
     pub fn new(code: ErrorCode, message: Option<String>) -> Self {
-        WitnessGenError { code, message }
+        WitnessGenError { code, message, params: None, retry_after_secs: None }
+    }
+
+    /// Restores the error from the `code`, `message` and `data` of a JSON-RPC error.
+    pub fn from_json_error(code: i64, message: String, data: Option<Value>) -> Self {
+        let data = data.and_then(|data| serde_json::from_value::<ErrorData>(data).ok());
+        let mut err = Self::new(ErrorCode::from(code), Some(message));
+        if let Some(data) = data {
+            err.params = data.params;
+            err.retry_after_secs = data.retry_after_secs;
+        }
+        err
+    }
+
+    pub fn with_params(mut self, params: Value) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    pub fn with_retry_after(mut self, secs: u64) -> Self {
+        self.retry_after_secs = Some(secs);
+        self
+    }
+
+    pub fn data(&self) -> ErrorData {
+        ErrorData {
+            kind: self.code.kind().to_string(),
+            params: self.params.clone(),
+            retry_after_secs: self.retry_after_secs,
+        }
     }
 
     pub fn to_json_error(&self) -> JsonError {
@@ -85,5 +247,127 @@ impl WitnessGenError {
 
     pub fn already_in_progress(message: String) -> Self {
         Self::new(ErrorCode::AlreadyInProgress, Some(message))
+            .with_retry_after(Self::IN_PROGRESS_RETRY_AFTER_SECS)
+    }
+
+    pub fn l2_block_not_found(message: String) -> Self {
+        Self::new(ErrorCode::L2BlockNotFound, Some(message))
+    }
+
+    pub fn l1_head_not_found(message: String) -> Self {
+        Self::new(ErrorCode::L1HeadNotFound, Some(message))
+    }
+
+    pub fn l1_head_not_descendant(message: String) -> Self {
+        Self::new(ErrorCode::L1HeadNotDescendant, Some(message))
+    }
+
+    pub fn blobs_unavailable(message: String) -> Self {
+        Self::new(ErrorCode::BlobsUnavailable, Some(message))
+    }
+
+    pub fn rpc_unavailable(message: String) -> Self {
+        Self::new(ErrorCode::RpcUnavailable, Some(message))
+    }
+
+    pub fn queue_full(message: String) -> Self {
+        Self::new(ErrorCode::QueueFull, Some(message))
+            .with_retry_after(Self::IN_PROGRESS_RETRY_AFTER_SECS)
+    }
+
+    pub fn timeout(message: String) -> Self {
+        Self::new(ErrorCode::Timeout, Some(message))
+    }
+
+    pub fn cancelled(message: String) -> Self {
+        Self::new(ErrorCode::Cancelled, Some(message))
+    }
+
+    pub fn internal(message: String) -> Self {
+        Self::new(ErrorCode::Internal, Some(message))
+    }
+
+    pub fn unauthorized(message: String) -> Self {
+        Self::new(ErrorCode::Unauthorized, Some(message))
+    }
+
+    pub fn execution_failed(message: String) -> Self {
+        Self::new(ErrorCode::ExecutionFailed, Some(message))
+    }
+
+    pub fn exceeds_cycle_budget(total_cycles: u64, max_cycles: u64) -> Self {
+        Self::new(
+            ErrorCode::ExceedsCycleBudget,
+            Some(format!("{} cycles exceed the budget of {}", total_cycles, max_cycles)),
+        )
+        .with_params(serde_json::json!({ "total_cycles": total_cycles, "max_cycles": max_cycles }))
+    }
+
+    pub fn config_mismatch(message: String) -> Self {
+        Self::new(ErrorCode::ConfigMismatch, Some(message))
+    }
+
+    pub fn degraded(unhealthy_endpoints: Vec<String>) -> Self {
+        Self::new(
+            ErrorCode::Degraded,
+            Some(format!("The witness generator is degraded: {:?} unhealthy", unhealthy_endpoints)),
+        )
+        .with_params(serde_json::json!({ "unhealthy_endpoints": unhealthy_endpoints }))
+        .with_retry_after(Self::DEGRADED_RETRY_AFTER_SECS)
+    }
+
+    pub fn not_yet_safe(message: String) -> Self {
+        Self::new(ErrorCode::NotYetSafe, Some(message))
+    }
+
+    pub fn l1_head_too_far(message: String) -> Self {
+        Self::new(ErrorCode::L1HeadTooFar, Some(message))
+    }
+
+    pub fn beyond_history_window(message: String) -> Self {
+        Self::new(ErrorCode::BeyondHistoryWindow, Some(message))
+    }
+
+    pub fn unknown_chain(chain_id: u64, served_chains: Vec<u64>) -> Self {
+        Self::new(
+            ErrorCode::UnknownChain,
+            Some(format!("Chain {} is not served: {:?} served", chain_id, served_chains)),
+        )
+        .with_params(serde_json::json!({ "chain_id": chain_id, "served_chains": served_chains }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_json_error_round_trip() {
+        let err = WitnessGenError::already_in_progress("Another request is in progress".into())
+            .with_params(json!({ "l2_hash": "0x01" }));
+        let json_err = err.to_json_error();
+        assert_eq!(json_err.code, JsonErrorCode::ServerError(1001));
+        assert_eq!(json_err.message, "Another request is in progress");
+        assert_eq!(json_err.data.as_ref().unwrap()["kind"], "AlreadyInProgress");
+
+        let restored =
+            WitnessGenError::from_json_error(json_err.code.code(), json_err.message, json_err.data);
+        assert!(matches!(restored.code, ErrorCode::AlreadyInProgress));
+        assert_eq!(restored.params, Some(json!({ "l2_hash": "0x01" })));
+        assert_eq!(restored.retry_after_secs, Some(WitnessGenError::IN_PROGRESS_RETRY_AFTER_SECS));
+    }
+
+    #[test]
+    fn test_unknown_code_does_not_panic() {
+        for code in 1000..=1019 {
+            assert_eq!(ErrorCode::from(code).code(), code);
+        }
+        assert_eq!(ErrorCode::from(4242), ErrorCode::Unknown(4242));
+
+        let code: ErrorCode = serde_json::from_value(json!(4242)).unwrap();
+        assert_eq!(code, ErrorCode::Unknown(4242));
+        assert_eq!(serde_json::to_value(code).unwrap(), json!(4242));
     }
 }
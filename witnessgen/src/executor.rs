@@ -1,52 +1,131 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tokio::{sync::mpsc::Receiver, task::JoinHandle};
+use tracing::Instrument;
 
 use crate::{
-    types::{TaskInfo, WitnessResult},
+    audit::{AuditEvent, AuditEventKind},
+    chain::Chain,
+    errors::JobError,
+    metrics::METRICS,
+    rollup_config::ResolvedRollupConfig,
+    types::{QueuedTask, TaskInfo, WitnessMetadata, WitnessResult},
     utils::generate_witness_impl,
     witness_db::WitnessDB,
 };
 
+pub static DEFAULT_MAX_RANGE_BLOCKS: u64 = 10;
+pub static DEFAULT_QUEUE_CAPACITY: usize = 10;
+pub static DEFAULT_JOB_COOLDOWN_MS: u64 = 1000;
+
+/// The configuration of the witness jobs.
+#[derive(Clone, Debug)]
+pub struct JobConfig {
+    /// The maximum number of L2 blocks a range witness can cover.
+    pub max_range_blocks: u64,
+    /// If `true`, a witness is marked completed without running it in the SP1 executor.
+    pub skip_simulation: bool,
+    /// The maximum number of cycles of a witness. A witness exceeding it is marked failed.
+    pub max_cycles: Option<u64>,
+    /// The rollup config resolved at startup, which every job fetches the data with.
+    pub rollup_config: Arc<ResolvedRollupConfig>,
+    /// How long the executor waits after a job before picking up the next one.
+    pub cooldown: Duration,
+}
+
+/// Executes the jobs of a chain one at a time.
 pub struct Executor {
-    rx: tokio::sync::mpsc::Receiver<TaskInfo>,
+    rx: tokio::sync::mpsc::Receiver<QueuedTask>,
+    chain: Arc<Chain>,
     witness_db: Arc<WitnessDB>,
+    config: JobConfig,
 }
 
 impl Executor {
-    pub fn new(rx: tokio::sync::mpsc::Receiver<TaskInfo>, witness_db: Arc<WitnessDB>) -> Self {
-        Self { rx, witness_db }
+    pub fn new(
+        rx: tokio::sync::mpsc::Receiver<QueuedTask>,
+        chain: Arc<Chain>,
+        witness_db: Arc<WitnessDB>,
+        config: JobConfig,
+    ) -> Self {
+        Self { rx, chain, witness_db, config }
     }
 
     pub async fn run(&mut self) {
-        while let Some(task_info) = self.rx.recv().await {
-            let l2_hash = task_info.l2_hash;
-            let l1_head_hash = task_info.l1_head_hash;
-
-            // Trying to generate a witness.
-            let sp1_stdin = generate_witness_impl(l2_hash, l1_head_hash).await;
-
-            // Store the witness to db.
-            match sp1_stdin {
-                Ok(value) => {
-                    tracing::info!("successfully witness result generated");
-                    self.witness_db.set(&l2_hash, &l1_head_hash, value.buffer).unwrap();
-                }
-                Err(e) => {
-                    tracing::info!("failed to generate witness: {:?}", e);
-                    self.witness_db
-                        .set(&l2_hash, &l1_head_hash, WitnessResult::EMPTY_WITNESS)
-                        .unwrap();
-                }
-            }
+        while let Some(queued_task) = self.rx.recv().await {
+            METRICS.queue_depth.dec();
+            drop(queued_task.queue_span);
+
+            // The job span is a child of the request span, so that the logs of a job can be
+            // followed by `user_req_id`.
+            let span = tracing::info_span!(
+                parent: &queued_task.span,
+                "job",
+                user_req_id = %queued_task.user_req_id,
+                queue_wait_ms = queued_task.queued_at.elapsed().as_millis() as u64
+            );
+            self.execute(&queued_task.task, &queued_task.user_req_id).instrument(span).await;
+
+            tokio::time::sleep(self.config.cooldown).await;
+        }
+    }
+
+    async fn execute(&self, task_info: &TaskInfo, user_req_id: &str) {
+        METRICS.active_workers.inc();
+        let status = &self.chain.status;
+        status.start_job(task_info, user_req_id);
+        tracing::info!("Start to generate witness: {:?}", task_info);
+        let audit_log = self.witness_db.audit_log();
+        let audit_event = |kind| AuditEvent::new(kind, task_info).with_user_req_id(user_req_id);
+        audit_log.record(audit_event(AuditEventKind::Started));
 
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        // Trying to generate a witness.
+        let timer = METRICS.phase_duration.with_label_values(&["total"]).start_timer();
+        let mut metadata = WitnessMetadata {
+            rollup_config_hash: Some(self.config.rollup_config.hash),
+            ..Default::default()
+        };
+        let sp1_stdin =
+            generate_witness_impl(task_info, &self.chain, &self.config, &mut metadata).await;
+        timer.observe_duration();
+
+        // Store the witness to db.
+        let _span = tracing::info_span!("db_write").entered();
+        status.enter_phase("db_write");
+        match sp1_stdin {
+            Ok(value) => {
+                tracing::info!("successfully witness result generated");
+                METRICS.jobs.with_label_values(&["completed"]).inc();
+                self.witness_db.set(task_info, value.buffer).unwrap();
+                self.witness_db.set_metadata(task_info, &metadata).unwrap();
+                audit_log.record(audit_event(AuditEventKind::Completed));
+            }
+            Err(e) => {
+                tracing::info!("failed to generate witness({}): {:?}", e.code.kind(), e);
+                METRICS.jobs.with_label_values(&["failed"]).inc();
+                METRICS.job_failures.with_label_values(&[e.code.kind()]).inc();
+                self.witness_db.set(task_info, WitnessResult::EMPTY_WITNESS).unwrap();
+                metadata.error = Some(JobError::from(&e));
+                self.witness_db.set_metadata(task_info, &metadata).unwrap();
+                audit_log.record(audit_event(AuditEventKind::Failed).with_detail(format!(
+                    "{}: {}",
+                    e.code.kind(),
+                    e.message.unwrap_or_default()
+                )));
+            }
         }
+        status.finish_job();
+        METRICS.active_workers.dec();
     }
 }
 
-pub async fn run(db: Arc<WitnessDB>, rx: Receiver<TaskInfo>) -> JoinHandle<()> {
+pub async fn run(
+    chain: Arc<Chain>,
+    db: Arc<WitnessDB>,
+    rx: Receiver<QueuedTask>,
+    config: JobConfig,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
-        let mut executor = Executor::new(rx, db);
+        let mut executor = Executor::new(rx, chain, db, config);
         executor.run().await;
     })
 }
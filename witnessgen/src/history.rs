@@ -0,0 +1,142 @@
+use alloy_primitives::Address;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::future::Future;
+
+use crate::{
+    chain::Chain,
+    checker::{fetch_rpc_data, quantity_u64, rpc_check_failed, Endpoint},
+    endpoints::EndpointPool,
+    errors::WitnessGenError,
+    status::StatusTracker,
+};
+
+/// The blocks of which an endpoint serves everything needed to generate a witness.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct HistoryWindow {
+    /// The oldest block at which every probe has passed.
+    pub oldest_block: u64,
+    /// The latest block when the window was probed.
+    pub latest_block: u64,
+}
+
+// The calls made for a witness at `block`: the headers and the receipts of L1 for the derivation,
+// and the headers and the state of L2 for the execution.
+fn probe_calls(endpoint: Endpoint, block: Value) -> Vec<(&'static str, Vec<Value>)> {
+    match endpoint {
+        Endpoint::L1 => {
+            vec![("debug_getRawHeader", vec![block.clone()]), ("debug_getRawReceipts", vec![block])]
+        }
+        Endpoint::L2 => vec![
+            ("debug_getRawHeader", vec![block.clone()]),
+            ("eth_getProof", vec![json!(Address::ZERO), json!([]), block]),
+        ],
+        Endpoint::L1Beacon | Endpoint::L2Node => vec![],
+    }
+}
+
+async fn probe(endpoints: &EndpointPool, endpoint: Endpoint, block_number: u64) -> bool {
+    let block = json!(format!("0x{:x}", block_number));
+    for (method, params) in probe_calls(endpoint, block) {
+        match fetch_rpc_data(endpoints, endpoint, method, params).await {
+            Ok(result) if !result.is_null() => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+// Returns the oldest block in `low..=high` passing `probe`, given that `high` passes and every block
+// after a passing one passes as well.
+async fn search_oldest<F, Fut>(mut low: u64, mut high: u64, probe: F) -> u64
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = bool>,
+{
+    if low < high && probe(low).await {
+        return low;
+    }
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if probe(mid).await {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    low
+}
+
+/// Finds the oldest block `endpoint` of `chain` serves by a binary search, and records it to the
+/// status of `chain`. Since the window only moves forward, the search starts from the oldest block
+/// found last time.
+pub async fn check_history_window(
+    chain: &Chain,
+    endpoint: Endpoint,
+) -> Result<HistoryWindow, WitnessGenError> {
+    let endpoints = &chain.endpoints;
+    let latest_block = fetch_rpc_data(endpoints, endpoint, "eth_blockNumber", vec![])
+        .await
+        .and_then(|block_number| quantity_u64(&block_number))
+        .map_err(|e| {
+            rpc_check_failed(endpoint, format!("Failed to fetch the latest block: {}", e))
+        })?;
+    if !probe(endpoints, endpoint, latest_block).await {
+        return Err(rpc_check_failed(
+            endpoint,
+            format!("{} does not serve the latest block {}", endpoint.env_key(), latest_block),
+        ));
+    }
+
+    let last_oldest_block =
+        chain.status.history_window(endpoint.name()).map_or(0, |w| w.oldest_block);
+    let oldest_block =
+        search_oldest(last_oldest_block.min(latest_block), latest_block, |block_number| {
+            probe(endpoints, endpoint, block_number)
+        })
+        .await;
+
+    let window = HistoryWindow { oldest_block, latest_block };
+    tracing::info!("History window of {}: {:?}", endpoint.name(), window);
+    chain.status.set_history_window(endpoint.name(), window.clone());
+    Ok(window)
+}
+
+/// Checks `block_number` is within the history window of `endpoint`. It passes if the window has
+/// not been probed yet.
+pub fn check_within_history(
+    status: &StatusTracker,
+    endpoint: Endpoint,
+    block_number: u64,
+) -> Result<(), WitnessGenError> {
+    match status.history_window(endpoint.name()) {
+        Some(window) if block_number < window.oldest_block => {
+            Err(WitnessGenError::beyond_history_window(format!(
+                "Block {} is older than the oldest block {} served by {}",
+                block_number,
+                window.oldest_block,
+                endpoint.env_key()
+            ))
+            .with_params(json!({
+                "endpoint": endpoint.name(),
+                "block_number": block_number,
+                "oldest_block": window.oldest_block,
+            })))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_search_oldest() {
+        let probe = |block_number: u64| async move { block_number >= 1234 };
+        assert_eq!(search_oldest(0, 5000, probe).await, 1234);
+        assert_eq!(search_oldest(1234, 5000, probe).await, 1234);
+        assert_eq!(search_oldest(2000, 5000, probe).await, 2000);
+        assert_eq!(search_oldest(5000, 5000, probe).await, 5000);
+    }
+}
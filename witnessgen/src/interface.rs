@@ -1,26 +1,46 @@
+mod endpoints;
 mod methods;
 
-use crate::{types::TaskInfo, witness_db::WitnessDB};
+use crate::{
+    auth::{read_auth_meta, ApiKeys, AuthMiddleware},
+    chain::ChainHandle,
+    config::ServerConfig,
+};
+use endpoints::HttpEndpoints;
+use jsonrpc_core::MetaIoHandler;
 use jsonrpc_http_server::ServerBuilder;
 use methods::{Rpc, RpcImpl};
-use std::sync::Arc;
-use tokio::sync::mpsc::Sender;
 
 pub static DEFAULT_WITNESS_STORE_PATH: &str = "data/witness_store";
 pub static DEFAULT_WITNESSGEN_RPC_ENDPOINT: &str = "0.0.0.0:3030";
+pub static DEFAULT_SERVER_THREADS: usize = 3;
+pub static DEFAULT_MAX_REQUEST_BODY_BYTES: usize = 200 * 1024 * 1024;
+
+/// Serves `chains` until the server is closed. The calls without `chain_id` are served by the first
+/// of `chains`.
+pub async fn run(chains: Vec<ChainHandle>, server_config: ServerConfig, api_keys: Option<ApiKeys>) {
+    if api_keys.is_none() {
+        tracing::warn!("API keys are not configured. Every call is allowed.");
+    }
 
-pub async fn run<T: ToString>(db: Arc<WitnessDB>, tx: Sender<TaskInfo>, endpoint: T) {
     // Run the server.
-    let mut io = jsonrpc_core::IoHandler::new();
-    io.extend_with(RpcImpl::new(tx, db).to_delegate());
+    let mut io = MetaIoHandler::with_middleware(AuthMiddleware::new(api_keys));
+    let max_request_body_bytes = server_config.max_request_body_bytes;
+    io.extend_with(RpcImpl::new(chains.clone(), max_request_body_bytes).to_delegate());
 
-    tracing::info!("Starting Witness Generator at {:?}", endpoint.to_string());
+    let chain_ids: Vec<u64> = chains.iter().map(ChainHandle::chain_id).collect();
+    tracing::info!(
+        "Starting Witness Generator at {:?} for chains {:?}",
+        server_config.endpoint,
+        chain_ids
+    );
     // NOTE(Ethan): We don't want this v3 verification key hash to be used.
     // tracing::info!("verification key hash: {:#?}", VERIFICATION_KEY_HASH.to_string());
-    let server = ServerBuilder::new(io)
-        .threads(3)
-        .max_request_body_size(200 * 1024 * 1024)
-        .start_http(&endpoint.to_string().parse().unwrap())
+    let server = ServerBuilder::with_meta_extractor(io, read_auth_meta)
+        .request_middleware(HttpEndpoints::new(chains))
+        .threads(server_config.threads)
+        .max_request_body_size(max_request_body_bytes)
+        .start_http(&server_config.endpoint.parse().unwrap())
         .unwrap();
 
     server.wait();
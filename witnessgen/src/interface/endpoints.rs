@@ -0,0 +1,70 @@
+use jsonrpc_http_server::{
+    hyper::{header::CONTENT_TYPE, Body, Method, Request, Response, StatusCode},
+    RequestMiddleware, RequestMiddlewareAction,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::{
+    chain::ChainHandle,
+    checker::check_readiness,
+    metrics::{METRICS, METRICS_PATH},
+};
+
+pub static HEALTHZ_PATH: &str = "/healthz";
+pub static READYZ_PATH: &str = "/readyz";
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_string(body).unwrap()))
+        .unwrap()
+}
+
+/// Serves the HTTP endpoints other than JSON-RPC. These endpoints do not require an API key.
+pub struct HttpEndpoints {
+    chains: Arc<Vec<ChainHandle>>,
+}
+
+impl HttpEndpoints {
+    pub fn new(chains: Vec<ChainHandle>) -> Self {
+        Self { chains: Arc::new(chains) }
+    }
+}
+
+impl RequestMiddleware for HttpEndpoints {
+    fn on_request(&self, request: Request<Body>) -> RequestMiddlewareAction {
+        if request.method() != Method::GET {
+            return request.into();
+        }
+
+        let path = request.uri().path();
+        if path == METRICS_PATH {
+            Response::builder()
+                .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                .body(Body::from(METRICS.encode()))
+                .unwrap()
+                .into()
+        } else if path == HEALTHZ_PATH {
+            json_response(StatusCode::OK, &json!({ "status": "ok" })).into()
+        } else if path == READYZ_PATH {
+            let chains = self.chains.clone();
+            RequestMiddlewareAction::Respond {
+                should_validate_hosts: true,
+                response: Box::pin(async move {
+                    let report = check_readiness(&chains);
+                    if !report.ready {
+                        tracing::warn!("Not ready: {:?}", report.checks);
+                    }
+                    let status =
+                        if report.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+                    Ok(json_response(status, &report))
+                }),
+            }
+        } else {
+            request.into()
+        }
+    }
+}
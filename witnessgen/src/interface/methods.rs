@@ -1,36 +1,166 @@
-use jsonrpc_core::Result as JsonResult;
+use jsonrpc_core::{BoxFuture, Result as JsonResult};
 use jsonrpc_derive::rpc;
 use kroma_zkvm_common::types::preprocessing;
-use std::sync::{Arc, RwLock};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{Instrument, Span};
 
+use crate::audit::{AuditEvent, AuditEventKind, AuditQuery};
+use crate::auth::AuthMeta;
+use crate::chain::ChainHandle;
+use crate::checker::{self, ValidationReport};
 use crate::errors::WitnessGenError;
-use crate::types::{RequestResult, SpecResult, TaskInfo, WitnessResult};
+use crate::metrics::METRICS;
+use crate::status::ServerStatus;
+use crate::types::{QueuedTask, RequestResult, SpecResult, TaskInfo, WitnessResult};
 use crate::utils::get_status_by_local_id;
-use crate::witness_db::WitnessDB;
 
 #[rpc]
 pub trait Rpc {
+    type Metadata;
+
     #[rpc(name = "spec")]
-    fn spec(&self) -> JsonResult<SpecResult>;
+    fn spec(&self, chain_id: Option<u64>) -> JsonResult<SpecResult>;
 
-    #[rpc(name = "requestWitness")]
-    fn request_witness(&self, l2_hash: String, l1_head_hash: String) -> JsonResult<RequestResult>;
+    #[rpc(meta, name = "requestWitness")]
+    fn request_witness(
+        &self,
+        meta: Self::Metadata,
+        l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> BoxFuture<JsonResult<RequestResult>>;
 
     #[rpc(name = "getWitness")]
-    fn get_witness(&self, l2_hash: String, l1_head_hash: String) -> JsonResult<WitnessResult>;
+    fn get_witness(
+        &self,
+        l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> JsonResult<WitnessResult>;
+
+    #[rpc(meta, name = "requestRangeWitness")]
+    fn request_range_witness(
+        &self,
+        meta: Self::Metadata,
+        start_l2_hash: String,
+        end_l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> BoxFuture<JsonResult<RequestResult>>;
+
+    #[rpc(name = "getRangeWitness")]
+    fn get_range_witness(
+        &self,
+        start_l2_hash: String,
+        end_l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> JsonResult<WitnessResult>;
+
+    #[rpc(name = "validateRequest")]
+    fn validate_request(
+        &self,
+        l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> BoxFuture<JsonResult<ValidationReport>>;
+
+    #[rpc(name = "getAuditLog")]
+    fn get_audit_log(
+        &self,
+        query: AuditQuery,
+        chain_id: Option<u64>,
+    ) -> JsonResult<Vec<AuditEvent>>;
+
+    #[rpc(name = "getServerStatus")]
+    fn get_server_status(&self, chain_id: Option<u64>) -> JsonResult<ServerStatus>;
 }
 
+#[derive(Clone)]
 pub struct RpcImpl {
-    pub tx: tokio::sync::mpsc::Sender<TaskInfo>,
-    pub current_task: Arc<RwLock<TaskInfo>>,
-    pub witness_db: Arc<WitnessDB>,
+    /// The chains served, keyed by the L2 chain ID.
+    pub chains: Arc<BTreeMap<u64, ChainRpc>>,
+    /// The chain of the calls without `chain_id`, which is the first chain of the config.
+    pub default_chain_id: u64,
+    pub max_request_body_bytes: usize,
 }
 
 impl RpcImpl {
-    pub fn new(tx: tokio::sync::mpsc::Sender<TaskInfo>, witness_db: Arc<WitnessDB>) -> Self {
-        RpcImpl { tx, current_task: Arc::new(RwLock::new(TaskInfo::default())), witness_db }
+    pub fn new(chains: Vec<ChainHandle>, max_request_body_bytes: usize) -> Self {
+        let default_chain_id = chains.first().map(ChainHandle::chain_id).unwrap_or_default();
+        let chains = chains
+            .into_iter()
+            .map(|handle| {
+                let chain =
+                    ChainRpc { handle, current_task: Arc::new(RwLock::new(TaskInfo::default())) };
+                (chain.handle.chain_id(), chain)
+            })
+            .collect();
+        RpcImpl { chains: Arc::new(chains), default_chain_id, max_request_body_bytes }
+    }
+
+    /// Returns the chain of `chain_id`, or the default one if it is not given.
+    fn chain(&self, chain_id: Option<u64>) -> JsonResult<ChainRpc> {
+        let chain_id = chain_id.unwrap_or(self.default_chain_id);
+        Span::current().record("chain_id", chain_id);
+        self.chains.get(&chain_id).cloned().ok_or_else(|| {
+            WitnessGenError::unknown_chain(chain_id, self.chains.keys().copied().collect())
+                .to_json_error()
+        })
+    }
+
+    /// Creates the span of an RPC call. `chain_id` and `user_req_id` are recorded once the
+    /// parameters are parsed.
+    fn request_span(method: &str) -> Span {
+        tracing::info_span!(
+            "rpc",
+            method,
+            chain_id = tracing::field::Empty,
+            user_req_id = tracing::field::Empty
+        )
+    }
+
+    fn parse_task(l2_hash: &str, l1_head_hash: &str) -> JsonResult<(TaskInfo, String)> {
+        let (l2_hash, l1_head_hash, user_req_id) =
+            preprocessing(l2_hash, l1_head_hash).map_err(|e| {
+                tracing::error!(
+                    "Invalid parameters - \"l2_hash\": {:?}, \"l1_head_hash\": {:?}",
+                    l2_hash,
+                    l1_head_hash
+                );
+                WitnessGenError::invalid_input_hash(e.to_string())
+                    .with_params(json!({ "l2_hash": l2_hash, "l1_head_hash": l1_head_hash }))
+                    .to_json_error()
+            })?;
+        Span::current().record("user_req_id", user_req_id.as_str());
+        Ok((TaskInfo::new(l2_hash, l1_head_hash), user_req_id))
+    }
+
+    fn parse_range_task(
+        start_l2_hash: &str,
+        end_l2_hash: &str,
+        l1_head_hash: &str,
+    ) -> JsonResult<(TaskInfo, String)> {
+        let (start, _) = Self::parse_task(start_l2_hash, l1_head_hash)?;
+        let (end, user_req_id) = Self::parse_task(end_l2_hash, l1_head_hash)?;
+        Ok((TaskInfo::new_range(start.l2_hash, end.l2_hash, end.l1_head_hash), user_req_id))
     }
+}
+
+/// The state of the RPC server for a chain. A chain runs a job at a time, which is `current_task`.
+#[derive(Clone)]
+pub struct ChainRpc {
+    pub handle: ChainHandle,
+    pub current_task: Arc<RwLock<TaskInfo>>,
+}
 
+impl ChainRpc {
     pub fn update_prev_req_status(&self) {
         let mut current_task = self.current_task.write().unwrap();
         if current_task.is_empty() {
@@ -38,16 +168,14 @@ impl RpcImpl {
         }
 
         // Flush `current_task` if the previous request has been completed.
-        if self.witness_db.get(&current_task.l2_hash, &current_task.l2_hash).is_some() {
+        if self.handle.witness_db.get(&current_task).is_some() {
             current_task.release();
         }
     }
 
     // Release `current_task` if the witness related to the `current_task` is already generated.
     pub fn release_current_task_if_completed(&self, current_task: &mut TaskInfo) {
-        if let Some(witness) =
-            self.witness_db.get(&current_task.l2_hash, &current_task.l1_head_hash)
-        {
+        if let Some(witness) = self.handle.witness_db.get(current_task) {
             if !witness.is_empty() {
                 current_task.release();
             }
@@ -56,34 +184,74 @@ impl RpcImpl {
 
     // Release `current_task` if the witness related to the `current_task` has been faild.
     pub fn release_current_task_if_failed(&self, current_task: &mut TaskInfo) {
-        if let Some(witness) =
-            self.witness_db.get(&current_task.l2_hash, &current_task.l1_head_hash)
-        {
+        if let Some(witness) = self.handle.witness_db.get(current_task) {
             if witness.is_empty() {
                 current_task.release();
             }
         }
     }
-}
 
-impl Rpc for RpcImpl {
-    fn spec(&self) -> JsonResult<SpecResult> {
-        let spec = SpecResult::default();
-        tracing::info!("Received sepc: {:?}", spec);
-        Ok(spec)
+    /// Checks `task` can be derived before its job is accepted. It is skipped while degraded, since
+    /// no new job is started anyway.
+    async fn preflight(&self, task: &TaskInfo) -> Result<(), WitnessGenError> {
+        let chain = &self.handle.chain;
+        if chain.status.is_degraded() {
+            return Ok(());
+        }
+        let config = &self.handle.job_config;
+        checker::preflight(chain, task, &config.rollup_config.config, config.max_range_blocks)
+            .await
+            .map(|_| ())
     }
 
-    fn request_witness(&self, l2_hash: String, l1_head_hash: String) -> JsonResult<RequestResult> {
-        let (l2_hash, l1_head_hash, user_req_id) =
-            preprocessing(&l2_hash, &l1_head_hash).map_err(|e| {
-                tracing::error!(
-                    "Invalid parameters - \"l2_hash\": {:?}, \"l1_head_hash\": {:?}",
-                    l2_hash,
-                    l1_head_hash
-                );
-                WitnessGenError::invalid_input_hash(e.to_string()).to_json_error()
-            })?;
+    /// Returns `true` if a request for `task` would start a new job, i.e., its witness is neither
+    /// cached nor in progress. Nothing is changed.
+    fn needs_new_job(&self, task: &TaskInfo) -> bool {
+        self.update_prev_req_status();
+
+        let mut current_task = self.current_task.write().unwrap();
+        self.release_current_task_if_completed(&mut current_task);
+        self.release_current_task_if_failed(&mut current_task);
+
+        let req_status =
+            get_status_by_local_id(&mut current_task, self.handle.witness_db.clone(), task, false);
+        matches!(req_status, Ok(RequestResult::Failed) | Ok(RequestResult::None))
+    }
+
+    /// Starts a job for `task` unless its witness is cached or in progress. The preflight checks,
+    /// which make several RPC calls, are run only if a new job would be started.
+    async fn request_task(
+        &self,
+        task: TaskInfo,
+        user_req_id: &str,
+        caller: Option<&str>,
+    ) -> JsonResult<RequestResult> {
         tracing::info!("Received request - user_req_id: {:?}", user_req_id);
+        let audit_log = self.handle.witness_db.audit_log();
+        let audit_event =
+            |kind| AuditEvent::new(kind, &task).with_user_req_id(user_req_id).with_caller(caller);
+        audit_log.record(audit_event(AuditEventKind::Requested));
+
+        if self.needs_new_job(&task) {
+            if let Err(err) = self.preflight(&task).await {
+                tracing::info!("{}: {:?}", err.code.kind(), err.message);
+                audit_log
+                    .record(audit_event(AuditEventKind::Rejected).with_detail(err.code.kind()));
+                return Err(err.to_json_error());
+            }
+        }
+        self.queue_task(task, user_req_id, caller)
+    }
+
+    fn queue_task(
+        &self,
+        task: TaskInfo,
+        user_req_id: &str,
+        caller: Option<&str>,
+    ) -> JsonResult<RequestResult> {
+        let audit_log = self.handle.witness_db.audit_log();
+        let audit_event =
+            |kind| AuditEvent::new(kind, &task).with_user_req_id(user_req_id).with_caller(caller);
 
         self.update_prev_req_status();
 
@@ -91,46 +259,60 @@ impl Rpc for RpcImpl {
         self.release_current_task_if_completed(&mut current_task);
         self.release_current_task_if_failed(&mut current_task);
 
-        let req_status = get_status_by_local_id(
-            &mut current_task,
-            self.witness_db.clone(),
-            &l2_hash,
-            &l1_head_hash,
-            true,
-        );
+        let req_status =
+            get_status_by_local_id(&mut current_task, self.handle.witness_db.clone(), &task, true);
         tracing::info!("Check the status of the request: {:?}, {:?}", user_req_id, req_status);
 
         match req_status {
             Ok(RequestResult::Completed) => Ok(RequestResult::Completed),
             Ok(RequestResult::Processing) => Ok(RequestResult::Processing),
             Ok(RequestResult::Failed) | Ok(RequestResult::None) => {
+                // NOTE: While degraded, the cached witnesses are still served but no new
+                // job is started, since it would fail deep inside the native host anyway.
+                let status = &self.handle.chain.status;
+                if status.is_degraded() {
+                    let err = WitnessGenError::degraded(status.unhealthy_endpoints());
+                    audit_log
+                        .record(audit_event(AuditEventKind::Rejected).with_detail(err.code.kind()));
+                    return Err(err.to_json_error());
+                }
                 tracing::info!("Start to generate witness");
-                current_task.set(l2_hash, l1_head_hash);
+                current_task.set(&task);
 
-                let tx = self.tx.clone();
-                tokio::task::spawn(async move {
-                    let task = TaskInfo { l2_hash, l1_head_hash };
-                    tx.send(task).await.unwrap();
-                });
+                METRICS.queue_depth.inc();
+                let queued_task = QueuedTask {
+                    task: task.clone(),
+                    user_req_id: user_req_id.to_string(),
+                    span: Span::current(),
+                    queue_span: tracing::info_span!("queue"),
+                    queued_at: Instant::now(),
+                };
+                if let Err(e) = self.handle.tx.try_send(queued_task) {
+                    METRICS.queue_depth.dec();
+                    current_task.release();
+                    let err = match e {
+                        TrySendError::Full(_) => WitnessGenError::queue_full(e.to_string()),
+                        TrySendError::Closed(_) => WitnessGenError::internal(e.to_string()),
+                    };
+                    audit_log
+                        .record(audit_event(AuditEventKind::Rejected).with_detail(err.code.kind()));
+                    return Err(err.to_json_error());
+                }
+                audit_log.record(audit_event(AuditEventKind::Queued));
                 Ok(RequestResult::Processing)
             }
             Err(e) => {
                 tracing::error!("{:?}", e);
-                Err(WitnessGenError::already_in_progress(e.to_string()).to_json_error())
+                let err = WitnessGenError::already_in_progress(e.to_string())
+                    .with_params(json!({ "request": task, "in_progress": *current_task }));
+                audit_log
+                    .record(audit_event(AuditEventKind::Rejected).with_detail(err.code.kind()));
+                Err(err.to_json_error())
             }
         }
     }
 
-    fn get_witness(&self, l2_hash: String, l1_head_hash: String) -> JsonResult<WitnessResult> {
-        let (l2_hash, l1_head_hash, user_req_id) =
-            preprocessing(&l2_hash, &l1_head_hash).map_err(|e| {
-                tracing::error!(
-                    "Invalid parameters - \"l2_hash\": {:?}, \"l1_head_hash\": {:?}",
-                    l2_hash,
-                    l1_head_hash
-                );
-                WitnessGenError::invalid_input_hash(e.to_string()).to_json_error()
-            })?;
+    fn get_task(&self, task: TaskInfo, user_req_id: &str) -> JsonResult<WitnessResult> {
         tracing::info!("Received get - user_req_id: {:?}", user_req_id);
         self.update_prev_req_status();
 
@@ -139,19 +321,21 @@ impl Rpc for RpcImpl {
         self.release_current_task_if_completed(&mut current_task);
         self.release_current_task_if_failed(&mut current_task);
 
-        let req_status = get_status_by_local_id(
-            &mut current_task,
-            self.witness_db.clone(),
-            &l2_hash,
-            &l1_head_hash,
-            false,
-        );
+        let req_status =
+            get_status_by_local_id(&mut current_task, self.handle.witness_db.clone(), &task, false);
 
         match req_status {
             Ok(RequestResult::Completed) => {
-                let witness = self.witness_db.get(&l2_hash, &l1_head_hash).unwrap();
+                let witness = self.handle.witness_db.get(&task).unwrap();
                 tracing::info!("Witness was found in db: {:?}", user_req_id);
-                Ok(WitnessResult::new_from_witness_buf(RequestResult::Completed, witness))
+                Ok(WitnessResult::new_from_witness_buf(RequestResult::Completed, witness)
+                    .with_metadata(self.handle.witness_db.get_metadata(&task)))
+            }
+            Ok(RequestResult::Failed) => {
+                tracing::info!("Check the status of the request: {:?}, Failed", user_req_id);
+                // The metadata carries the error of the job, e.g., the cycles beyond the budget.
+                Ok(WitnessResult::new_with_status(RequestResult::Failed)
+                    .with_metadata(self.handle.witness_db.get_metadata(&task)))
             }
             Ok(status) => {
                 tracing::info!("Check the status of the request: {:?}, {:?}", user_req_id, status);
@@ -168,3 +352,135 @@ impl Rpc for RpcImpl {
         }
     }
 }
+
+impl Rpc for RpcImpl {
+    type Metadata = AuthMeta;
+
+    fn spec(&self, chain_id: Option<u64>) -> JsonResult<SpecResult> {
+        METRICS.requests.with_label_values(&["spec"]).inc();
+        let _span = Self::request_span("spec").entered();
+        let chain = self.chain(chain_id)?;
+        let spec = SpecResult::default()
+            .with_chains(self.chains.keys().copied().collect())
+            .with_rollup_config_hash(chain.handle.job_config.rollup_config.hash);
+        tracing::info!("Received sepc: {:?}", spec);
+        Ok(spec)
+    }
+
+    fn request_witness(
+        &self,
+        meta: AuthMeta,
+        l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> BoxFuture<JsonResult<RequestResult>> {
+        METRICS.requests.with_label_values(&["requestWitness"]).inc();
+        let rpc = self.clone();
+        Box::pin(
+            async move {
+                let chain = rpc.chain(chain_id)?;
+                let (task, user_req_id) = Self::parse_task(&l2_hash, &l1_head_hash)?;
+                chain.request_task(task, &user_req_id, meta.caller.as_deref()).await
+            }
+            .instrument(Self::request_span("requestWitness")),
+        )
+    }
+
+    fn get_witness(
+        &self,
+        l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> JsonResult<WitnessResult> {
+        METRICS.requests.with_label_values(&["getWitness"]).inc();
+        let _span = Self::request_span("getWitness").entered();
+        let chain = self.chain(chain_id)?;
+        let (task, user_req_id) = Self::parse_task(&l2_hash, &l1_head_hash)?;
+        chain.get_task(task, &user_req_id)
+    }
+
+    fn request_range_witness(
+        &self,
+        meta: AuthMeta,
+        start_l2_hash: String,
+        end_l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> BoxFuture<JsonResult<RequestResult>> {
+        METRICS.requests.with_label_values(&["requestRangeWitness"]).inc();
+        let rpc = self.clone();
+        Box::pin(
+            async move {
+                let chain = rpc.chain(chain_id)?;
+                let (task, user_req_id) =
+                    Self::parse_range_task(&start_l2_hash, &end_l2_hash, &l1_head_hash)?;
+                chain.request_task(task, &user_req_id, meta.caller.as_deref()).await
+            }
+            .instrument(Self::request_span("requestRangeWitness")),
+        )
+    }
+
+    fn get_range_witness(
+        &self,
+        start_l2_hash: String,
+        end_l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> JsonResult<WitnessResult> {
+        METRICS.requests.with_label_values(&["getRangeWitness"]).inc();
+        let _span = Self::request_span("getRangeWitness").entered();
+        let chain = self.chain(chain_id)?;
+        let (task, user_req_id) =
+            Self::parse_range_task(&start_l2_hash, &end_l2_hash, &l1_head_hash)?;
+        chain.get_task(task, &user_req_id)
+    }
+
+    fn validate_request(
+        &self,
+        l2_hash: String,
+        l1_head_hash: String,
+        chain_id: Option<u64>,
+    ) -> BoxFuture<JsonResult<ValidationReport>> {
+        METRICS.requests.with_label_values(&["validateRequest"]).inc();
+        let rpc = self.clone();
+        Box::pin(
+            async move {
+                let chain = rpc.chain(chain_id)?;
+                let (task, user_req_id) = Self::parse_task(&l2_hash, &l1_head_hash)?;
+                tracing::info!("Received validate - user_req_id: {:?}", user_req_id);
+                let config = &chain.handle.job_config;
+                let rollup_config = &config.rollup_config.config;
+                checker::validate(
+                    &chain.handle.chain,
+                    &task,
+                    rollup_config,
+                    config.max_range_blocks,
+                )
+                .await
+                .map_err(|e| e.to_json_error())
+            }
+            .instrument(Self::request_span("validateRequest")),
+        )
+    }
+
+    fn get_audit_log(
+        &self,
+        query: AuditQuery,
+        chain_id: Option<u64>,
+    ) -> JsonResult<Vec<AuditEvent>> {
+        METRICS.requests.with_label_values(&["getAuditLog"]).inc();
+        let _span = Self::request_span("getAuditLog").entered();
+        let chain = self.chain(chain_id)?;
+        chain.handle.witness_db.audit_log().query(&query).map_err(|e| {
+            WitnessGenError::internal(format!("Failed to read the audit log: {}", e))
+                .to_json_error()
+        })
+    }
+
+    fn get_server_status(&self, chain_id: Option<u64>) -> JsonResult<ServerStatus> {
+        METRICS.requests.with_label_values(&["getServerStatus"]).inc();
+        let _span = Self::request_span("getServerStatus").entered();
+        let chain = self.chain(chain_id)?;
+        Ok(chain.handle.chain.status.report(&chain.handle, self.max_request_body_bytes))
+    }
+}
@@ -1,7 +1,21 @@
+pub mod audit;
+pub mod auth;
+pub mod chain;
 pub mod checker;
+pub mod config;
+pub mod endpoints;
 pub mod errors;
 pub mod executor;
+pub mod history;
 pub mod interface;
+pub mod metrics;
+pub mod monitor;
+pub mod registry;
+pub mod resources;
+pub mod rollup_config;
+pub mod rpc_counter;
+pub mod status;
+pub mod telemetry;
 pub mod types;
 pub mod utils;
 pub mod version;
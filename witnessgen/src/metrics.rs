@@ -0,0 +1,180 @@
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGauge, Opts, Registry, TextEncoder,
+};
+
+pub static METRICS_PATH: &str = "/metrics";
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+/// Prometheus metrics of the witness generator.
+pub struct Metrics {
+    registry: Registry,
+    /// The number of RPC calls by method.
+    pub requests: IntCounterVec,
+    /// The number of finished jobs by outcome (`completed` or `failed`).
+    pub jobs: IntCounterVec,
+    /// The number of failed jobs by the kind of the error.
+    pub job_failures: IntCounterVec,
+    /// The duration of each phase of a job in seconds.
+    pub phase_duration: HistogramVec,
+    /// The number of tasks sent to the executor but not yet picked up.
+    pub queue_depth: IntGauge,
+    /// The number of jobs being executed.
+    pub active_workers: IntGauge,
+    /// The number of witnesses in the `WitnessDB`.
+    pub db_entries: IntGauge,
+    /// The size of the `WitnessDB` on disk in bytes.
+    pub db_bytes: IntGauge,
+    /// The number of witnesses evicted from the `WitnessDB` by its capacity or expiration.
+    pub db_evictions: IntCounter,
+    /// The number of failed RPC checks by endpoint.
+    pub rpc_check_failures: IntCounterVec,
+    /// The number of chains which have an unhealthy endpoint and reject new requests, i.e., `1` if
+    /// the only chain is degraded.
+    pub degraded: IntGauge,
+    /// The number of times an endpoint has switched to another of its URLs.
+    pub endpoint_failovers: IntCounterVec,
+    /// The peak RSS of the native host of a job in bytes.
+    pub job_peak_rss_bytes: Histogram,
+    /// The CPU time of the native host of a job in seconds.
+    pub job_cpu_seconds: Histogram,
+    /// The bytes written to the data directory of the native host by a job.
+    pub job_data_dir_bytes: Histogram,
+    /// The number of the methods of the data fetcher called by the jobs.
+    pub fetcher_rpc_calls: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("witnessgen".to_string()), None).unwrap();
+
+        let requests = IntCounterVec::new(
+            Opts::new("requests_total", "The number of RPC calls by method"),
+            &["method"],
+        )
+        .unwrap();
+        let jobs = IntCounterVec::new(
+            Opts::new("jobs_total", "The number of finished jobs by outcome"),
+            &["outcome"],
+        )
+        .unwrap();
+        let job_failures = IntCounterVec::new(
+            Opts::new("job_failures_total", "The number of failed jobs by error kind"),
+            &["kind"],
+        )
+        .unwrap();
+        // NOTE: A job takes a few minutes, so the buckets range from 1s to about an hour.
+        let phase_duration = HistogramVec::new(
+            HistogramOpts::new("job_phase_duration_seconds", "The duration of each phase of a job")
+                .buckets(exponential_buckets(1.0, 2.0, 12).unwrap()),
+            &["phase"],
+        )
+        .unwrap();
+        let queue_depth = IntGauge::new("queue_depth", "The number of queued tasks").unwrap();
+        let active_workers =
+            IntGauge::new("active_workers", "The number of jobs being executed").unwrap();
+        let db_entries = IntGauge::new("db_entries", "The number of stored witnesses").unwrap();
+        let db_bytes = IntGauge::new("db_bytes", "The size of the witness store in bytes").unwrap();
+        let db_evictions =
+            IntCounter::new("db_evictions_total", "The number of evicted witnesses").unwrap();
+        let rpc_check_failures = IntCounterVec::new(
+            Opts::new("rpc_check_failures_total", "The number of failed RPC checks by endpoint"),
+            &["endpoint"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(jobs.clone())).unwrap();
+        registry.register(Box::new(job_failures.clone())).unwrap();
+        registry.register(Box::new(phase_duration.clone())).unwrap();
+        registry.register(Box::new(queue_depth.clone())).unwrap();
+        registry.register(Box::new(active_workers.clone())).unwrap();
+        registry.register(Box::new(db_entries.clone())).unwrap();
+        registry.register(Box::new(db_bytes.clone())).unwrap();
+        registry.register(Box::new(db_evictions.clone())).unwrap();
+        // NOTE: The native host takes from hundreds of MiB to tens of GiB of memory and disk.
+        let job_peak_rss_bytes = Histogram::with_opts(
+            HistogramOpts::new("job_peak_rss_bytes", "The peak RSS of the native host of a job")
+                .buckets(exponential_buckets(64.0 * 1024.0 * 1024.0, 2.0, 12).unwrap()),
+        )
+        .unwrap();
+        let job_cpu_seconds = Histogram::with_opts(
+            HistogramOpts::new("job_cpu_seconds", "The CPU time of the native host of a job")
+                .buckets(exponential_buckets(1.0, 2.0, 14).unwrap()),
+        )
+        .unwrap();
+        let job_data_dir_bytes = Histogram::with_opts(
+            HistogramOpts::new("job_data_dir_bytes", "The bytes written to the data dir by a job")
+                .buckets(exponential_buckets(1024.0 * 1024.0, 4.0, 10).unwrap()),
+        )
+        .unwrap();
+        let fetcher_rpc_calls = IntCounter::new(
+            "fetcher_rpc_calls_total",
+            "The number of the RPC requests sent by the data fetchers of the jobs",
+        )
+        .unwrap();
+        let degraded =
+            IntGauge::new("degraded", "The number of chains with an unhealthy endpoint").unwrap();
+        let endpoint_failovers = IntCounterVec::new(
+            Opts::new(
+                "endpoint_failovers_total",
+                "The number of times an endpoint has switched to another of its URLs",
+            ),
+            &["endpoint"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(rpc_check_failures.clone())).unwrap();
+        registry.register(Box::new(job_peak_rss_bytes.clone())).unwrap();
+        registry.register(Box::new(job_cpu_seconds.clone())).unwrap();
+        registry.register(Box::new(job_data_dir_bytes.clone())).unwrap();
+        registry.register(Box::new(fetcher_rpc_calls.clone())).unwrap();
+        registry.register(Box::new(degraded.clone())).unwrap();
+        registry.register(Box::new(endpoint_failovers.clone())).unwrap();
+
+        Self {
+            registry,
+            requests,
+            jobs,
+            job_failures,
+            phase_duration,
+            queue_depth,
+            active_workers,
+            db_entries,
+            db_bytes,
+            db_evictions,
+            rpc_check_failures,
+            degraded,
+            endpoint_failovers,
+            job_peak_rss_bytes,
+            job_cpu_seconds,
+            job_data_dir_bytes,
+            fetcher_rpc_calls,
+        }
+    }
+
+    /// Encodes all the metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        TextEncoder::new().encode_to_string(&self.registry.gather()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::METRICS;
+
+    #[test]
+    fn test_encode() {
+        METRICS.jobs.with_label_values(&["completed"]).inc();
+        METRICS.phase_duration.with_label_values(&["native_host"]).observe(3.0);
+
+        let encoded = METRICS.encode();
+        assert!(encoded.contains("witnessgen_jobs_total{outcome=\"completed\"}"));
+        assert!(
+            encoded.contains("witnessgen_job_phase_duration_seconds_bucket{phase=\"native_host\"")
+        );
+        assert!(encoded.contains("witnessgen_queue_depth"));
+    }
+}
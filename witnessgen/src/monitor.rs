@@ -0,0 +1,46 @@
+use std::time::Duration;
+use tokio::{
+    task::JoinHandle,
+    time::{Instant, MissedTickBehavior},
+};
+use tracing::Instrument;
+
+use crate::{
+    chain::{Chain, ChainHandle},
+    checker::{check_endpoints, Endpoint},
+    history::check_history_window,
+};
+
+pub static DEFAULT_HEALTH_CHECK_INTERVAL_SECS: u64 = 30;
+
+/// Runs the checks of the endpoints of each of `chains` and their history windows every `interval`
+/// in the background. The results are recorded to the status of the chain, which puts the chain in
+/// the degraded mode while any of its endpoints is unhealthy.
+pub async fn run(chains: Vec<ChainHandle>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        // The endpoints have just been checked at startup, so the first check is after `interval`.
+        let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+        // NOTE: The checks may take longer than the interval if an endpoint hangs, so the
+        // missed ticks are not caught up.
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            for handle in &chains {
+                let span = tracing::info_span!("health_check", chain_id = handle.chain_id());
+                check_chain(&handle.chain).instrument(span).await;
+            }
+        }
+    })
+}
+
+async fn check_chain(chain: &Chain) {
+    let checks = check_endpoints(chain).await;
+    for (endpoint, check) in checks.iter().filter(|(_, check)| !check.ok) {
+        tracing::warn!("Health check of {} failed: {:?}", endpoint, check.error);
+    }
+    for endpoint in [Endpoint::L1, Endpoint::L2] {
+        if let Err(e) = check_history_window(chain, endpoint).await {
+            tracing::warn!("History check of {} failed: {:?}", endpoint.name(), e.message);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use alloy_primitives::B256;
+use op_alloy_genesis::RollupConfig;
+use op_succinct_client_utils::{boot::BootInfoStruct, BootInfoWithBytesConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sp1_sdk::SP1Stdin;
+
+use crate::{
+    checker::{
+        diff_rollup_config, fetch_rpc_data, json_u64, rpc_check_failed, ConfigMismatch, Endpoint,
+    },
+    endpoints::EndpointPool,
+    errors::WitnessGenError,
+};
+
+/// The rollup configs shipped in `configs/<l2_chain_id>/rollup.json`, keyed by the L2 chain ID.
+static EMBEDDED_ROLLUP_CONFIGS: [(u64, &str); 3] = [
+    (255, include_str!("../../configs/255/rollup.json")),
+    (2358, include_str!("../../configs/2358/rollup.json")),
+    (7791, include_str!("../../configs/7791/rollup.json")),
+];
+
+/// A rollup config built into the binary.
+#[derive(Clone, Debug)]
+pub struct RegisteredRollupConfig {
+    pub l2_chain_id: u64,
+    /// The JSON of `config` as shipped, which the native host reads.
+    pub json: &'static str,
+    pub config: RollupConfig,
+    /// The hash of `json`. See [`rollup_config_hash`].
+    pub hash: B256,
+}
+
+/// The fields of a rollup config built into the binary which differ from the ones of L2 Node.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RollupConfigDiff {
+    pub l2_chain_id: u64,
+    /// The hash of the rollup config built into the binary.
+    pub hash: B256,
+    pub mismatches: Vec<ConfigMismatch>,
+}
+
+/// Returns the L2 chain IDs of the rollup configs built into the binary in ascending order.
+pub fn chain_ids() -> Vec<u64> {
+    EMBEDDED_ROLLUP_CONFIGS.iter().map(|(l2_chain_id, _)| *l2_chain_id).collect()
+}
+
+/// Returns the rollup config of `l2_chain_id` built into the binary. It fails if there is none, or
+/// if it does not parse or is of another chain.
+pub fn embedded(l2_chain_id: u64) -> Result<RegisteredRollupConfig, WitnessGenError> {
+    let json = EMBEDDED_ROLLUP_CONFIGS
+        .iter()
+        .find(|(id, _)| *id == l2_chain_id)
+        .map(|(_, json)| *json)
+        .ok_or_else(|| {
+            WitnessGenError::internal(format!(
+                "No rollup config is embedded for L2 chain {}",
+                l2_chain_id
+            ))
+        })?;
+    let config = parse_rollup_config(json.as_bytes(), "the embedded rollup config")?;
+    if config.l2_chain_id != l2_chain_id {
+        return Err(WitnessGenError::config_mismatch(format!(
+            "The rollup config embedded for L2 chain {} is of L2 chain {}",
+            l2_chain_id, config.l2_chain_id
+        )));
+    }
+    Ok(RegisteredRollupConfig {
+        l2_chain_id,
+        json,
+        config,
+        hash: rollup_config_hash(json.as_bytes()),
+    })
+}
+
+pub(crate) fn parse_rollup_config(
+    bytes: &[u8],
+    name: &str,
+) -> Result<RollupConfig, WitnessGenError> {
+    serde_json::from_slice(bytes)
+        .map_err(|e| WitnessGenError::internal(format!("Failed to parse {}: {}", name, e)))
+}
+
+/// Returns the hash of the rollup config file `json` the native host reads, which identifies the
+/// config a witness is generated with. The stdin of the program carries the bytes of the file as
+/// its `rollup_config_bytes`, so it is the `rollupConfigHash` the program derives in
+/// `BootInfoStruct` from them. `json` must be a valid rollup config.
+///
+/// NOTE: The program built with the `kroma` feature does not commit the rollup config, so the hash
+/// is not checked by a proof.
+pub fn rollup_config_hash(json: &[u8]) -> B256 {
+    boot_info_rollup_config_hash(BootInfoWithBytesConfig {
+        l1_head: Default::default(),
+        l2_output_root: Default::default(),
+        l2_claim: Default::default(),
+        l2_claim_block: 0,
+        chain_id: 0,
+        rollup_config_bytes: json.to_vec(),
+    })
+}
+
+/// Returns the rollup config hash the program derives from the boot info at the head of `stdin`.
+pub fn stdin_rollup_config_hash(stdin: &SP1Stdin) -> Result<B256, WitnessGenError> {
+    let boot_info = stdin
+        .buffer
+        .first()
+        .ok_or_else(|| WitnessGenError::internal("The stdin has no boot info".to_string()))
+        .and_then(|bytes| {
+            bincode::deserialize::<BootInfoWithBytesConfig>(bytes).map_err(|e| {
+                WitnessGenError::internal(format!("Failed to decode the boot info: {}", e))
+            })
+        })?;
+    Ok(boot_info_rollup_config_hash(boot_info))
+}
+
+fn boot_info_rollup_config_hash(boot_info: BootInfoWithBytesConfig) -> B256 {
+    B256::from_slice(BootInfoStruct::from(boot_info).rollupConfigHash.as_slice())
+}
+
+/// Compares the rollup config built into the binary for the chain of L2 Node with the one of L2
+/// Node field by field.
+pub async fn diff_embedded(endpoints: &EndpointPool) -> Result<RollupConfigDiff, WitnessGenError> {
+    let node_rollup_config: Value =
+        fetch_rpc_data(endpoints, Endpoint::L2Node, "optimism_rollupConfig", vec![])
+            .await
+            .map_err(|e| {
+                rpc_check_failed(Endpoint::L2Node, format!("Failed to fetch rollup config: {}", e))
+            })?;
+    let l2_chain_id = json_u64(&node_rollup_config, "/l2_chain_id").map_err(|e| {
+        WitnessGenError::internal(format!("Invalid rollup config of L2 Node: {}", e))
+    })?;
+
+    let registered = embedded(l2_chain_id)?;
+    let embedded_rollup_config: Value = serde_json::from_str(registered.json).map_err(|e| {
+        WitnessGenError::internal(format!("Failed to parse the embedded rollup config: {}", e))
+    })?;
+    Ok(RollupConfigDiff {
+        l2_chain_id,
+        hash: registered.hash,
+        mismatches: diff_rollup_config("", &embedded_rollup_config, &node_rollup_config),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_rollup_configs() {
+        assert_eq!(chain_ids(), vec![255, 2358, 7791]);
+        for l2_chain_id in chain_ids() {
+            let registered = embedded(l2_chain_id).unwrap();
+            assert_eq!(registered.config.l2_chain_id, l2_chain_id);
+
+            // The stdin `get_proof_stdin` builds for the native host reading the file.
+            let mut stdin = SP1Stdin::new();
+            stdin.write(&BootInfoWithBytesConfig {
+                l1_head: Default::default(),
+                l2_output_root: Default::default(),
+                l2_claim: Default::default(),
+                l2_claim_block: 1,
+                chain_id: l2_chain_id,
+                rollup_config_bytes: registered.json.as_bytes().to_vec(),
+            });
+            stdin.write_vec(vec![0; 32]);
+            assert_eq!(stdin_rollup_config_hash(&stdin).unwrap(), registered.hash);
+        }
+        assert!(embedded(10).is_err());
+        assert!(stdin_rollup_config_hash(&SP1Stdin::new()).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::HashMap, fs, future::Future, path::Path, time::Duration};
+use tokio::{
+    sync::{oneshot, Mutex},
+    task::JoinHandle,
+};
+
+static SAMPLING_INTERVAL: Duration = Duration::from_millis(500);
+// NOTE: `USER_HZ` is 100 on every Linux platform we run on, so the ticks in `/proc/<pid>/stat`
+// are converted without calling `sysconf(_SC_CLK_TCK)`.
+static CLOCK_TICKS_PER_SEC: u64 = 100;
+
+/// The resource usage of some subprocesses and their subprocesses.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessTreeUsage {
+    /// The peak of the sum of the RSS of the processes in bytes.
+    pub peak_rss_bytes: u64,
+    /// The CPU time (user + system) spent by the processes in milliseconds.
+    pub cpu_time_ms: u64,
+}
+
+/// Awaits `spawn`, which spawns subprocesses of this process, and returns its output with the PIDs
+/// of the subprocesses it has spawned, which are told by the subprocesses of this process before and
+/// after it. The spawns are serialized, so the subprocesses of the jobs running concurrently are not
+/// mixed up. On the platforms without `/proc`, no PID is returned.
+pub async fn spawn_tracked<F: Future>(spawn: F) -> (F::Output, Vec<u32>) {
+    static SPAWN_LOCK: Mutex<()> = Mutex::const_new(());
+
+    let _guard = SPAWN_LOCK.lock().await;
+    let server = std::process::id();
+    let before = children(&read_proc_stats(), server);
+    let output = spawn.await;
+    let pids = children(&read_proc_stats(), server)
+        .into_iter()
+        .filter(|pid| !before.contains(pid))
+        .collect();
+    (output, pids)
+}
+
+/// Samples the processes of `roots` and their subprocesses from `/proc` until it is stopped. On the
+/// platforms without `/proc`, the usage is always zero.
+pub struct ProcessTreeSampler {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<ProcessTreeUsage>,
+}
+
+impl ProcessTreeSampler {
+    pub fn start(roots: Vec<u32>) -> Self {
+        let (stop, mut stopped) = oneshot::channel();
+        let handle = tokio::spawn(async move {
+            let mut samples = Samples::default();
+            loop {
+                samples.sample(&roots);
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = tokio::time::sleep(SAMPLING_INTERVAL) => {}
+                }
+            }
+            samples.usage()
+        });
+        Self { stop, handle }
+    }
+
+    pub async fn stop(self) -> ProcessTreeUsage {
+        let _ = self.stop.send(());
+        self.handle.await.unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+struct Samples {
+    peak_rss_bytes: u64,
+    // The latest CPU ticks spent by each process itself. The ticks of its reaped children are left
+    // out, since they have been sampled as processes of the tree as well. The ticks a process spends
+    // after its last sample are not counted.
+    cpu_ticks: HashMap<u32, u64>,
+}
+
+impl Samples {
+    fn sample(&mut self, roots: &[u32]) {
+        let stats = read_proc_stats();
+        let mut tree = roots.to_vec();
+        let mut rss_bytes = 0;
+        let mut i = 0;
+        while i < tree.len() {
+            let pid = tree[i];
+            // A process which has exited keeps its last CPU ticks.
+            if let Some(stat) = stats.iter().find(|stat| stat.pid == pid) {
+                rss_bytes += read_rss_bytes(pid);
+                self.cpu_ticks.insert(pid, stat.cpu_ticks);
+            }
+            tree.extend(children(&stats, pid));
+            i += 1;
+        }
+        self.peak_rss_bytes = self.peak_rss_bytes.max(rss_bytes);
+    }
+
+    fn usage(&self) -> ProcessTreeUsage {
+        let cpu_ticks: u64 = self.cpu_ticks.values().sum();
+        ProcessTreeUsage {
+            peak_rss_bytes: self.peak_rss_bytes,
+            cpu_time_ms: cpu_ticks * 1000 / CLOCK_TICKS_PER_SEC,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ProcStat {
+    pid: u32,
+    ppid: u32,
+    cpu_ticks: u64,
+}
+
+impl ProcStat {
+    fn read(pid: u32) -> Option<Self> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        Self::parse(pid, &stat)
+    }
+
+    // The second field is the command name in parentheses which may contain spaces, so the fields
+    // are split after its closing parenthesis. See `man 5 proc`.
+    fn parse(pid: u32, stat: &str) -> Option<Self> {
+        let fields: Vec<&str> = stat.get(stat.rfind(')')? + 2..)?.split_whitespace().collect();
+        let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+        let ppid = field(4)? as u32;
+        // utime + stime
+        let cpu_ticks = field(14)? + field(15)?;
+        Some(Self { pid, ppid, cpu_ticks })
+    }
+}
+
+fn read_proc_stats() -> Vec<ProcStat> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(ProcStat::read)
+        .collect()
+}
+
+fn children(stats: &[ProcStat], pid: u32) -> Vec<u32> {
+    stats.iter().filter(|stat| stat.ppid == pid).map(|stat| stat.pid).collect()
+}
+
+fn read_rss_bytes(pid: u32) -> u64 {
+    fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+            line.split_whitespace().nth(1)?.parse::<u64>().ok()
+        })
+        .map(|kb| kb * 1024)
+        .unwrap_or(0)
+}
+
+/// Returns the total size of the files under `path` in bytes.
+pub fn dir_size(path: &Path) -> u64 {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::read_dir(path)
+            .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+            .unwrap_or(0),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let stat = "4242 (native host) S 4200 4242 4200 0 -1 4194304 100 0 0 0 \
+                    250 50 10 5 20 0 8 0 100 1000000 2000 18446744073709551615";
+        assert_eq!(
+            ProcStat::parse(4242, stat),
+            Some(ProcStat { pid: 4242, ppid: 4200, cpu_ticks: 300 })
+        );
+        assert_eq!(ProcStat::parse(4242, "4242 (broken"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_sample_subprocess() {
+        let spawn = async { std::process::Command::new("sleep").arg("1").spawn().unwrap() };
+        let (mut child, pids) = spawn_tracked(spawn).await;
+        assert!(pids.contains(&child.id()));
+        let mut other = std::process::Command::new("sleep").arg("1").spawn().unwrap();
+
+        // Only the given process is sampled, not another subprocess of this process.
+        let mut samples = Samples::default();
+        samples.sample(&[child.id()]);
+        assert_eq!(samples.cpu_ticks.keys().collect::<Vec<_>>(), vec![&child.id()]);
+        assert!(samples.peak_rss_bytes > 0);
+
+        let sampler = ProcessTreeSampler::start(vec![child.id()]);
+        tokio::time::sleep(SAMPLING_INTERVAL * 2).await;
+        let usage = sampler.stop().await;
+        child.wait().unwrap();
+        other.wait().unwrap();
+        assert!(usage.peak_rss_bytes > 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sample_reaped_subprocess() {
+        // The shell burns CPU in a subshell, reaps it and sleeps.
+        let script = "(i=0; while [ $i -lt 300000 ]; do i=$((i+1)); done); sleep 1";
+        let mut root = std::process::Command::new("sh").args(["-c", script]).spawn().unwrap();
+        let pid = root.id();
+        let is_sleeping = || {
+            children(&read_proc_stats(), pid).iter().any(|child| {
+                fs::read_to_string(format!("/proc/{}/comm", child))
+                    .is_ok_and(|comm| comm.trim() == "sleep")
+            })
+        };
+
+        let mut samples = Samples::default();
+        while !is_sleeping() {
+            samples.sample(&[pid]);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        samples.sample(&[pid]);
+        // utime + stime + cutime + cstime of the shell, which includes the reaped subshell.
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap();
+        let fields: Vec<u64> = stat[stat.rfind(')').unwrap() + 2..]
+            .split_whitespace()
+            .skip(11)
+            .take(4)
+            .map(|field| field.parse().unwrap())
+            .collect();
+        let total_ticks: u64 = fields.iter().sum();
+        root.wait().unwrap();
+
+        let sampled_ticks: u64 = samples.cpu_ticks.values().sum();
+        assert!(total_ticks >= 20);
+        assert!(sampled_ticks <= total_ticks);
+        assert!(sampled_ticks + 5 >= total_ticks);
+    }
+}
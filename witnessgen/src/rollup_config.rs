@@ -0,0 +1,312 @@
+use alloy::providers::ProviderBuilder;
+use alloy_primitives::B256;
+use op_alloy_genesis::RollupConfig;
+use op_succinct_host_utils::fetcher::{OPSuccinctDataFetcher, RPCConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{env, fmt, fs, path::PathBuf, str::FromStr, sync::Arc};
+
+use crate::{
+    checker::{
+        fetch_l2_rpc_data, fetch_rpc_data, quantity_u64, rollup_config_path, rpc_check_failed,
+        Endpoint,
+    },
+    endpoints::EndpointPool,
+    errors::WitnessGenError,
+    registry::{self, parse_rollup_config, rollup_config_hash},
+};
+
+/// Where the rollup config is read from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum RollupConfigSource {
+    /// `optimism_rollupConfig` of L2 Node.
+    Rpc,
+    /// A JSON file.
+    File(PathBuf),
+    /// The config of the L2 chain built into the binary.
+    Embedded,
+}
+
+impl FromStr for RollupConfigSource {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s.to_string()))
+    }
+}
+
+impl From<String> for RollupConfigSource {
+    /// Parses `rpc`, `embedded` or the path of a JSON file.
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "rpc" => Self::Rpc,
+            "embedded" => Self::Embedded,
+            _ => Self::File(PathBuf::from(s)),
+        }
+    }
+}
+
+impl From<RollupConfigSource> for String {
+    fn from(source: RollupConfigSource) -> Self {
+        source.to_string()
+    }
+}
+
+impl fmt::Display for RollupConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rpc => write!(f, "rpc"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Embedded => write!(f, "embedded"),
+        }
+    }
+}
+
+impl RollupConfigSource {
+    /// Before the MPT time, L2 Geth has no `optimism` chain config and L2 Node does not serve the
+    /// rollup config of the program, so it is read from `configs/<l2_chain_id>/rollup.json`.
+    pub async fn detect(
+        endpoints: &EndpointPool,
+        l2_chain_id: u64,
+    ) -> Result<Self, WitnessGenError> {
+        let chain_config = fetch_l2_rpc_data(endpoints, "debug_chainConfig", vec![])
+            .await
+            .map_err(|e| rpc_check_failed(Endpoint::L2, format!("L2 Geth is not alive: {}", e)))?;
+        if chain_config.get("optimism").is_none() {
+            tracing::info!("It should be before MPT time. The rollup config is read from a file.");
+            Ok(Self::File(rollup_config_path(l2_chain_id)))
+        } else {
+            Ok(Self::Rpc)
+        }
+    }
+}
+
+/// The rollup config resolved once at startup. Every data fetcher is created with it.
+#[derive(Clone, Debug)]
+pub struct ResolvedRollupConfig {
+    pub source: RollupConfigSource,
+    pub config: RollupConfig,
+    /// The hash of the file of `path`. See [`rollup_config_hash`].
+    pub hash: B256,
+    /// The JSON file of `config` the native host reads. It is the file of the `File` source, and a
+    /// file in the temporary directory otherwise.
+    pub path: PathBuf,
+}
+
+impl ResolvedRollupConfig {
+    /// Reads the rollup config of the L2 chain of `endpoints` from `source`. If `source` is `None`,
+    /// it is detected by [`RollupConfigSource::detect`].
+    pub async fn resolve(
+        endpoints: &EndpointPool,
+        source: Option<RollupConfigSource>,
+    ) -> Result<Self, WitnessGenError> {
+        let l2_chain_id = fetch_rpc_data(endpoints, Endpoint::L2, "eth_chainId", vec![])
+            .await
+            .and_then(|chain_id| quantity_u64(&chain_id))
+            .map_err(|e| rpc_check_failed(Endpoint::L2, format!("L2 Geth is not alive: {}", e)))?;
+        let source = match source {
+            Some(source) => source,
+            None => RollupConfigSource::detect(endpoints, l2_chain_id).await?,
+        };
+
+        let (config, json) = match &source {
+            RollupConfigSource::Rpc => {
+                let config = fetch_rollup_config(endpoints).await?;
+                let json = serde_json::to_vec_pretty(&config).map_err(|e| {
+                    WitnessGenError::internal(format!("Failed to serialize rollup config: {}", e))
+                })?;
+                (config, json)
+            }
+            RollupConfigSource::File(path) => {
+                let bytes = fs::read(path).map_err(|e| {
+                    WitnessGenError::internal(format!("Failed to read {}: {}", path.display(), e))
+                })?;
+                (parse_rollup_config(&bytes, &path.display().to_string())?, bytes)
+            }
+            RollupConfigSource::Embedded => {
+                let registered = registry::embedded(l2_chain_id)?;
+                (registered.config, registered.json.as_bytes().to_vec())
+            }
+        };
+        if config.l2_chain_id != l2_chain_id {
+            return Err(WitnessGenError::config_mismatch(format!(
+                "The rollup config from {} is of L2 chain {}, but L2 Geth is of {}",
+                source, config.l2_chain_id, l2_chain_id
+            )));
+        }
+
+        let hash = rollup_config_hash(&json);
+        let path = match &source {
+            RollupConfigSource::File(path) => path.clone(),
+            _ => write_host_rollup_config(l2_chain_id, hash, &json)?,
+        };
+        tracing::info!(
+            "Rollup config of chain {}: source {}, hash {}, file {}",
+            l2_chain_id,
+            source,
+            hash,
+            path.display()
+        );
+
+        Ok(Self { source, config, hash, path })
+    }
+
+    /// Creates a data fetcher with the rollup config. It fetches the data from the URL each of
+    /// `endpoints` is currently served by.
+    pub fn fetcher(
+        &self,
+        endpoints: &EndpointPool,
+    ) -> Result<OPSuccinctDataFetcher, WitnessGenError> {
+        self.fetcher_with(|endpoint| {
+            endpoints.url(endpoint).map_err(|e| WitnessGenError::rpc_unavailable(e.to_string()))
+        })
+    }
+
+    /// Creates a data fetcher with the rollup config. It fetches the data from the URL `url` returns
+    /// for each endpoint.
+    pub fn fetcher_with(
+        &self,
+        url: impl Fn(Endpoint) -> Result<String, WitnessGenError>,
+    ) -> Result<OPSuccinctDataFetcher, WitnessGenError> {
+        let provider = |endpoint: Endpoint| -> Result<_, WitnessGenError> {
+            let url = url(endpoint)?.parse().map_err(|e| {
+                WitnessGenError::internal(format!("Invalid URL of {}: {}", endpoint.name(), e))
+            })?;
+            Ok(Arc::new(ProviderBuilder::default().on_http(url)))
+        };
+        Ok(OPSuccinctDataFetcher {
+            rpc_config: RPCConfig {
+                l1_rpc: url(Endpoint::L1)?,
+                l1_beacon_rpc: url(Endpoint::L1Beacon)?,
+                l2_rpc: url(Endpoint::L2)?,
+                l2_node_rpc: url(Endpoint::L2Node)?,
+            },
+            l1_provider: provider(Endpoint::L1)?,
+            l2_provider: provider(Endpoint::L2)?,
+            rollup_config: Some(self.config.clone()),
+        })
+    }
+}
+
+/// Fetches the rollup config of L2 Node and completes it with the chain config of L2 Geth.
+async fn fetch_rollup_config(endpoints: &EndpointPool) -> Result<RollupConfig, WitnessGenError> {
+    let node_rollup_config =
+        fetch_rpc_data(endpoints, Endpoint::L2Node, "optimism_rollupConfig", vec![])
+            .await
+            .map_err(|e| {
+                rpc_check_failed(Endpoint::L2Node, format!("Failed to fetch rollup config: {}", e))
+            })?;
+    let chain_config = fetch_l2_rpc_data(endpoints, "debug_chainConfig", vec![])
+        .await
+        .map_err(|e| rpc_check_failed(Endpoint::L2, format!("L2 Geth is not alive: {}", e)))?;
+    merge_rollup_config(node_rollup_config, &chain_config)
+}
+
+// L2 Node does not serve the base fee params, so they are taken from the `optimism` chain config
+// of L2 Geth, which encodes them as integers while the rollup config encodes them as quantities.
+fn merge_rollup_config(
+    mut rollup_config: Value,
+    chain_config: &Value,
+) -> Result<RollupConfig, WitnessGenError> {
+    let param = |key: &str| {
+        chain_config.pointer(&format!("/optimism/{}", key)).and_then(Value::as_u64).ok_or_else(
+            || WitnessGenError::internal(format!("The chain config has no optimism.{}", key)),
+        )
+    };
+    let elasticity = param("eip1559Elasticity")?;
+    let base_fee_params = |denominator: u64| {
+        json!({
+            "max_change_denominator": format!("0x{:x}", denominator),
+            "elasticity_multiplier": format!("0x{:x}", elasticity),
+        })
+    };
+    let fields = rollup_config.as_object_mut().ok_or_else(|| {
+        WitnessGenError::internal("The rollup config of L2 Node is not an object".to_string())
+    })?;
+    fields.insert("base_fee_params".to_string(), base_fee_params(param("eip1559Denominator")?));
+    if let Ok(denominator) = param("eip1559DenominatorCanyon") {
+        fields.insert("canyon_base_fee_params".to_string(), base_fee_params(denominator));
+    }
+    serde_json::from_value(rollup_config).map_err(|e| {
+        WitnessGenError::internal(format!("Failed to parse the rollup config of L2 Node: {}", e))
+    })
+}
+
+// The native host reads the rollup config from a file. It is written to the temporary directory
+// rather than to `configs/`, which is tracked by the repository, and named by its hash so that the
+// chains and the processes sharing the directory never overwrite each other's config.
+fn write_host_rollup_config(
+    l2_chain_id: u64,
+    hash: B256,
+    json: &[u8],
+) -> Result<PathBuf, WitnessGenError> {
+    let dir = env::temp_dir().join("witnessgen-rollup-configs");
+    let path = dir.join(format!("{}-{}.json", l2_chain_id, hash));
+    if fs::read(&path).is_ok_and(|current| current == json) {
+        return Ok(path);
+    }
+    fs::create_dir_all(&dir).and_then(|_| fs::write(&path, json)).map_err(|e| {
+        WitnessGenError::internal(format!("Failed to write {}: {}", path.display(), e))
+    })?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+    use tokio::runtime::Runtime;
+
+    use super::*;
+
+    #[test]
+    fn test_rollup_config_source() {
+        assert_eq!("rpc".parse::<RollupConfigSource>().unwrap(), RollupConfigSource::Rpc);
+        assert_eq!("embedded".parse::<RollupConfigSource>().unwrap(), RollupConfigSource::Embedded);
+        let source = "configs/255/rollup.json".parse::<RollupConfigSource>().unwrap();
+        assert_eq!(source, RollupConfigSource::File(PathBuf::from("configs/255/rollup.json")));
+        assert_eq!(source.to_string(), "configs/255/rollup.json");
+    }
+
+    #[test]
+    fn test_merge_rollup_config() {
+        let registered = registry::embedded(255).unwrap();
+        let mut node_rollup_config: Value = serde_json::from_str(registered.json).unwrap();
+        let fields = node_rollup_config.as_object_mut().unwrap();
+        fields.remove("base_fee_params");
+        fields.remove("canyon_base_fee_params");
+        let chain_config = json!({
+            "chainId": 255,
+            "optimism": {
+                "eip1559Elasticity": 6,
+                "eip1559Denominator": 50,
+                "eip1559DenominatorCanyon": 250
+            }
+        });
+        let config = merge_rollup_config(node_rollup_config.clone(), &chain_config).unwrap();
+        assert_eq!(config, registered.config);
+
+        assert!(merge_rollup_config(node_rollup_config, &json!({ "chainId": 255 })).is_err());
+    }
+
+    #[test]
+    fn test_online_rollup_config() {
+        let _ = Command::new("cp")
+            .args(["-r", "../configs", "."])
+            .output()
+            .expect("Failed to copy .env file");
+
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            dotenv::dotenv().ok();
+            let endpoints = EndpointPool::from_env();
+            let rollup_config = ResolvedRollupConfig::resolve(&endpoints, None).await.unwrap();
+            assert!(rollup_config.path.exists());
+        });
+
+        let _ = Command::new("rm")
+            .args(["-rf", "./configs"])
+            .output()
+            .expect("Failed to copy .env file");
+    }
+}
@@ -0,0 +1,196 @@
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::oneshot;
+
+use crate::{
+    checker::Endpoint, endpoints::EndpointPool, errors::WitnessGenError, metrics::METRICS,
+};
+
+/// A proxy on localhost which forwards the requests to each endpoint and counts them. op-succinct
+/// sends some RPC calls of the data fetcher with its own HTTP clients rather than the providers of
+/// the fetcher, so the fetcher is pointed at the proxy to count all of them. It stops when dropped.
+pub struct RpcCounter {
+    addr: SocketAddr,
+    upstreams: Arc<BTreeMap<&'static str, String>>,
+    requests: Arc<AtomicU64>,
+    _stop: oneshot::Sender<()>,
+}
+
+impl RpcCounter {
+    /// Starts the proxy to the URL each of `endpoints` is currently served by.
+    pub fn start(endpoints: &EndpointPool) -> Result<Self, WitnessGenError> {
+        let upstreams = Endpoint::ALL
+            .into_iter()
+            .map(|endpoint| Ok((endpoint.name(), endpoints.url(endpoint)?)))
+            .collect::<anyhow::Result<BTreeMap<_, _>>>()
+            .map_err(|e| WitnessGenError::rpc_unavailable(e.to_string()))?;
+        let proxy = Arc::new(Proxy {
+            upstreams: Arc::new(upstreams),
+            client: reqwest::Client::new(),
+            requests: Arc::new(AtomicU64::new(0)),
+        });
+
+        let server = Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0))).map_err(|e| {
+            WitnessGenError::internal(format!("Failed to start RPC counter: {}", e))
+        })?;
+        let service = proxy.clone();
+        let server = server.serve(make_service_fn(move |_| {
+            let proxy = service.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let proxy = proxy.clone();
+                    async move { Ok::<_, Infallible>(proxy.forward(req).await) }
+                }))
+            }
+        }));
+        let addr = server.local_addr();
+        let (stop, stopped) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async move {
+            stopped.await.ok();
+        }));
+
+        Ok(Self {
+            addr,
+            upstreams: proxy.upstreams.clone(),
+            requests: proxy.requests.clone(),
+            _stop: stop,
+        })
+    }
+
+    /// Returns the URL of the proxy to `endpoint`.
+    pub fn url(&self, endpoint: Endpoint) -> String {
+        format!("http://{}/{}", self.addr, endpoint.name())
+    }
+
+    /// Returns the URL the requests to `endpoint` are forwarded to.
+    pub fn upstream(&self, endpoint: Endpoint) -> &str {
+        &self.upstreams[endpoint.name()]
+    }
+
+    /// Returns the number of the requests sent to the proxy so far.
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+}
+
+struct Proxy {
+    upstreams: Arc<BTreeMap<&'static str, String>>,
+    client: reqwest::Client,
+    requests: Arc<AtomicU64>,
+}
+
+impl Proxy {
+    async fn forward(&self, req: Request<Body>) -> Response<Body> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        METRICS.fetcher_rpc_calls.inc();
+        self.try_forward(req).await.unwrap_or_else(|e| {
+            let mut res = Response::new(Body::from(e));
+            *res.status_mut() = StatusCode::BAD_GATEWAY;
+            res
+        })
+    }
+
+    // The path after the name of the endpoint and the query are appended to its URL, so the
+    // REST API of L1 Beacon is proxied as well as JSON-RPC.
+    async fn try_forward(&self, req: Request<Body>) -> Result<Response<Body>, String> {
+        let path = req.uri().path().trim_start_matches('/');
+        let (name, rest) = path.split_once('/').unwrap_or((path, ""));
+        let upstream =
+            self.upstreams.get(name).ok_or_else(|| format!("Unknown endpoint: {}", name))?;
+        let mut url = reqwest::Url::parse(upstream).map_err(|e| e.to_string())?;
+        if !rest.is_empty() {
+            let path = format!("{}/{}", url.path().trim_end_matches('/'), rest);
+            url.set_path(&path);
+        }
+        if let Some(query) = req.uri().query() {
+            let query = match url.query() {
+                Some(upstream_query) => format!("{}&{}", upstream_query, query),
+                None => query.to_string(),
+            };
+            url.set_query(Some(&query));
+        }
+
+        let method = reqwest::Method::from_bytes(req.method().as_str().as_bytes())
+            .map_err(|e| e.to_string())?;
+        let content_type = req.headers().get(CONTENT_TYPE).map(|value| value.as_bytes().to_vec());
+        let body = hyper::body::to_bytes(req.into_body()).await.map_err(|e| e.to_string())?;
+        let mut request = self.client.request(method, url).body(body.to_vec());
+        if let Some(content_type) = content_type {
+            request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+        }
+        // The URL is left out of the errors, since it may contain an API key.
+        let res = request.send().await.map_err(|e| e.without_url().to_string())?;
+
+        let status = res.status().as_u16();
+        let content_type =
+            res.headers().get(reqwest::header::CONTENT_TYPE).map(|v| v.as_bytes().to_vec());
+        let body = res.bytes().await.map_err(|e| e.without_url().to_string())?;
+        let mut builder = Response::builder().status(status);
+        if let Some(content_type) = content_type {
+            builder = builder.header(CONTENT_TYPE, content_type);
+        }
+        builder.body(Body::from(body.to_vec())).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Serves the method, path and body of each request back.
+    async fn start_echo_server() -> String {
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service_fn(
+            |_| async {
+                Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                    let head = format!("{} {}", req.method(), req.uri());
+                    let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                    let echo = format!("{} {}", head, String::from_utf8_lossy(&body));
+                    Ok::<_, Infallible>(Response::new(Body::from(echo)))
+                }))
+            },
+        ));
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    #[tokio::test]
+    async fn test_rpc_counter() {
+        let upstream = start_echo_server().await;
+        let urls = Endpoint::ALL
+            .into_iter()
+            .map(|endpoint| {
+                (endpoint, vec![format!("{}/{}?key=secret", upstream, endpoint.name())])
+            })
+            .collect();
+        let counter = RpcCounter::start(&EndpointPool::new(urls)).unwrap();
+        assert_eq!(counter.upstream(Endpoint::L2), format!("{}/l2?key=secret", upstream));
+
+        let client = reqwest::Client::new();
+        let res = client.post(counter.url(Endpoint::L2)).body("{}").send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "POST /l2?key=secret {}");
+        let url = format!("{}/eth/v1/node/version?a=b", counter.url(Endpoint::L1Beacon));
+        let res = client.get(url).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap(), "GET /l1_beacon/eth/v1/node/version?key=secret&a=b ");
+        let res = client.get(format!("http://{}/unknown", counter.addr)).send().await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_GATEWAY);
+        assert_eq!(counter.requests(), 3);
+
+        let url = counter.url(Endpoint::L2);
+        drop(counter);
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(client.post(url).body("{}").send().await.is_err());
+    }
+}
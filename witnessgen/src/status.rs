@@ -0,0 +1,345 @@
+use alloy_primitives::B256;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    chain::ChainHandle,
+    checker::BeaconInfo,
+    endpoints::UrlStatus,
+    history::HistoryWindow,
+    metrics::METRICS,
+    types::{SpecResult, TaskInfo},
+};
+
+/// The number of the recent checks kept for each endpoint.
+static HEALTH_HISTORY_LEN: usize = 20;
+
+/// Tracks the state of a chain which is not kept by the metrics, e.g., the phase of the current job
+/// and the last result of each RPC check.
+pub struct StatusTracker {
+    started_at: Instant,
+    current_job: RwLock<Option<JobProgress>>,
+    rpc_checks: RwLock<BTreeMap<String, EndpointHealth>>,
+    degraded: AtomicBool,
+    l1_beacon: RwLock<Option<BeaconInfo>>,
+    history_windows: RwLock<BTreeMap<String, HistoryWindow>>,
+}
+
+impl Default for StatusTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct JobProgress {
+    task: TaskInfo,
+    user_req_id: String,
+    phase: &'static str,
+    started_at: Instant,
+    phase_started_at: Instant,
+}
+
+impl StatusTracker {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            current_job: RwLock::new(None),
+            rpc_checks: RwLock::new(BTreeMap::new()),
+            degraded: AtomicBool::new(false),
+            l1_beacon: RwLock::new(None),
+            history_windows: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn start_job(&self, task: &TaskInfo, user_req_id: &str) {
+        let now = Instant::now();
+        *self.current_job.write().unwrap() = Some(JobProgress {
+            task: task.clone(),
+            user_req_id: user_req_id.to_string(),
+            phase: "started",
+            started_at: now,
+            phase_started_at: now,
+        });
+    }
+
+    /// Moves the current job to `phase`, which is one of the labels of `phase_duration`.
+    pub fn enter_phase(&self, phase: &'static str) {
+        if let Some(job) = self.current_job.write().unwrap().as_mut() {
+            job.phase = phase;
+            job.phase_started_at = Instant::now();
+        }
+    }
+
+    pub fn finish_job(&self) {
+        *self.current_job.write().unwrap() = None;
+    }
+
+    pub fn current_job(&self) -> Option<CurrentJob> {
+        self.current_job.read().unwrap().as_ref().map(|job| CurrentJob {
+            task: job.task.clone(),
+            user_req_id: job.user_req_id.clone(),
+            phase: job.phase.to_string(),
+            elapsed_secs: job.started_at.elapsed().as_secs(),
+            phase_elapsed_secs: job.phase_started_at.elapsed().as_secs(),
+        })
+    }
+
+    /// Records the result of a check of `endpoint`. `error` is `None` if it passed. The server is
+    /// degraded while the last check of any endpoint has failed.
+    pub fn record_check(&self, endpoint: &str, error: Option<String>) {
+        let checked_at =
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let check = RpcCheckStatus { ok: error.is_none(), error, checked_at };
+
+        let mut rpc_checks = self.rpc_checks.write().unwrap();
+        rpc_checks.entry(endpoint.to_string()).or_default().push(check);
+        let unhealthy: Vec<&String> =
+            rpc_checks.iter().filter(|(_, health)| !health.healthy).map(|(name, _)| name).collect();
+        let degraded = !unhealthy.is_empty();
+        if self.degraded.swap(degraded, Ordering::SeqCst) != degraded {
+            if degraded {
+                tracing::warn!("Degraded: {:?} unhealthy. New requests are rejected.", unhealthy);
+                METRICS.degraded.inc();
+            } else {
+                tracing::info!("Recovered: every endpoint is healthy.");
+                METRICS.degraded.dec();
+            }
+        }
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::SeqCst)
+    }
+
+    /// Returns the names of the endpoints whose last check has failed.
+    pub fn unhealthy_endpoints(&self) -> Vec<String> {
+        let rpc_checks = self.rpc_checks.read().unwrap();
+        rpc_checks
+            .iter()
+            .filter(|(_, health)| !health.healthy)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Returns the last check of each endpoint, keyed by the name of the endpoint.
+    pub fn last_checks(&self) -> BTreeMap<String, RpcCheckStatus> {
+        let rpc_checks = self.rpc_checks.read().unwrap();
+        rpc_checks
+            .iter()
+            .filter_map(|(name, health)| Some((name.clone(), health.history.back()?.clone())))
+            .collect()
+    }
+
+    pub fn set_l1_beacon(&self, beacon_info: BeaconInfo) {
+        *self.l1_beacon.write().unwrap() = Some(beacon_info);
+    }
+
+    pub fn l1_beacon(&self) -> Option<BeaconInfo> {
+        self.l1_beacon.read().unwrap().clone()
+    }
+
+    pub fn set_history_window(&self, endpoint: &str, window: HistoryWindow) {
+        self.history_windows.write().unwrap().insert(endpoint.to_string(), window);
+    }
+
+    pub fn history_window(&self, endpoint: &str) -> Option<HistoryWindow> {
+        self.history_windows.read().unwrap().get(endpoint).cloned()
+    }
+
+    /// Reports the status of the chain of `handle`, which this tracks.
+    pub fn report(&self, handle: &ChainHandle, max_request_body_bytes: usize) -> ServerStatus {
+        let spec = SpecResult::default();
+        let (witness_db, config) = (&handle.witness_db, &handle.job_config);
+        let queue_capacity = handle.tx.max_capacity();
+        ServerStatus {
+            version: spec.version,
+            sp1_version: spec.sp1_version,
+            program_key: spec.program_key,
+            chain_id: handle.chain_id(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            workers: WorkerStatus {
+                active: self.current_job.read().unwrap().is_some() as u64,
+                queue_depth: (queue_capacity - handle.tx.capacity()) as u64,
+                queue_capacity,
+            },
+            current_job: self.current_job(),
+            witness_db: WitnessDBStatus {
+                entries: witness_db.num_entries(),
+                capacity: witness_db.capacity(),
+                size_bytes: witness_db.size_in_bytes(),
+            },
+            degraded: self.is_degraded(),
+            rpc_checks: self.rpc_checks.read().unwrap().clone(),
+            endpoints: handle.chain.endpoints.report(),
+            limits: ServerLimits {
+                max_range_blocks: config.max_range_blocks,
+                skip_simulation: config.skip_simulation,
+                max_cycles: config.max_cycles,
+                max_request_body_bytes,
+            },
+            rollup_config_hash: Some(config.rollup_config.hash),
+            l1_beacon: self.l1_beacon(),
+            history_windows: self.history_windows.read().unwrap().clone(),
+        }
+    }
+}
+
+/// The response of `getServerStatus`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub version: String,
+    pub sp1_version: String,
+    pub program_key: String,
+    /// The L2 chain the status is of.
+    pub chain_id: u64,
+    pub uptime_secs: u64,
+    pub workers: WorkerStatus,
+    pub current_job: Option<CurrentJob>,
+    pub witness_db: WitnessDBStatus,
+    /// If `true`, an endpoint of the chain is unhealthy and new requests to it are rejected.
+    pub degraded: bool,
+    /// The health of each RPC endpoint, keyed by the name of the endpoint.
+    pub rpc_checks: BTreeMap<String, EndpointHealth>,
+    /// The URLs of each endpoint and which of them it is served by.
+    pub endpoints: BTreeMap<String, Vec<UrlStatus>>,
+    pub limits: ServerLimits,
+    /// The hash of the rollup config resolved at startup. See `registry::rollup_config_hash`.
+    pub rollup_config_hash: Option<B256>,
+    /// The network and the blob retention of the L1 beacon found by its last successful check.
+    pub l1_beacon: Option<BeaconInfo>,
+    /// The blocks each of L1 Geth and L2 Geth serves for a witness.
+    pub history_windows: BTreeMap<String, HistoryWindow>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    /// The number of jobs being executed.
+    pub active: u64,
+    /// The number of tasks sent to the executor but not yet picked up.
+    pub queue_depth: u64,
+    pub queue_capacity: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CurrentJob {
+    pub task: TaskInfo,
+    pub user_req_id: String,
+    /// `started`, `fetch`, `native_host`, `stdin`, `execute` or `db_write`.
+    pub phase: String,
+    pub elapsed_secs: u64,
+    pub phase_elapsed_secs: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WitnessDBStatus {
+    pub entries: usize,
+    pub capacity: usize,
+    pub size_bytes: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RpcCheckStatus {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix timestamp in seconds.
+    pub checked_at: u64,
+}
+
+/// The health of an endpoint and its recent checks.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct EndpointHealth {
+    /// If `true`, the last check has passed.
+    pub healthy: bool,
+    pub consecutive_failures: u64,
+    /// Unix timestamp in seconds of the last passed check.
+    pub last_ok_at: Option<u64>,
+    /// The recent checks, the oldest first.
+    pub history: VecDeque<RpcCheckStatus>,
+}
+
+impl EndpointHealth {
+    fn push(&mut self, check: RpcCheckStatus) {
+        self.healthy = check.ok;
+        if check.ok {
+            self.consecutive_failures = 0;
+            self.last_ok_at = Some(check.checked_at);
+        } else {
+            self.consecutive_failures += 1;
+        }
+        if self.history.len() == HEALTH_HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(check);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerLimits {
+    pub max_range_blocks: u64,
+    pub skip_simulation: bool,
+    pub max_cycles: Option<u64>,
+    pub max_request_body_bytes: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::b256;
+
+    use super::*;
+
+    #[test]
+    fn test_job_progress() {
+        let tracker = StatusTracker::new();
+        assert_eq!(tracker.current_job(), None);
+
+        let task = TaskInfo::new(
+            b256!("0000000000000000000000000000000000000000000000000000000000000001"),
+            b256!("0000000000000000000000000000000000000000000000000000000000000002"),
+        );
+        tracker.start_job(&task, "req");
+        tracker.enter_phase("native_host");
+        let job = tracker.current_job().unwrap();
+        assert_eq!(job.task, task);
+        assert_eq!(job.user_req_id, "req");
+        assert_eq!(job.phase, "native_host");
+
+        tracker.finish_job();
+        assert_eq!(tracker.current_job(), None);
+    }
+
+    #[test]
+    fn test_record_check() {
+        let tracker = StatusTracker::new();
+        tracker.record_check("l1", None);
+        tracker.record_check("l2", None);
+        assert!(!tracker.is_degraded());
+
+        tracker.record_check("l2", Some("L2 Geth is not alive".to_string()));
+        tracker.record_check("l2", Some("L2 Geth is not alive".to_string()));
+        assert!(tracker.is_degraded());
+        assert_eq!(tracker.unhealthy_endpoints(), vec!["l2".to_string()]);
+        {
+            let checks = tracker.rpc_checks.read().unwrap();
+            assert_eq!(checks["l2"].consecutive_failures, 2);
+            assert_eq!(checks["l2"].history.len(), 3);
+            assert_eq!(checks["l2"].history[2].error.as_deref(), Some("L2 Geth is not alive"));
+        }
+        assert!(!tracker.last_checks()["l2"].ok);
+
+        tracker.record_check("l2", None);
+        assert!(!tracker.is_degraded());
+        assert!(tracker.last_checks()["l2"].ok);
+        for _ in 0..HEALTH_HISTORY_LEN {
+            tracker.record_check("l1", None);
+        }
+        assert_eq!(tracker.rpc_checks.read().unwrap()["l1"].history.len(), HEALTH_HISTORY_LEN);
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use tracing_subscriber::{
+    filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt, Layer,
+};
+
+/// The format of the logs written to stdout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, including the fields of the current span and its parents.
+    Json,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub struct TelemetryArgs {
+    /// The format of the logs. `json` prints one object per line with the `user_req_id` of the
+    /// request being handled.
+    #[clap(long = "log-format", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// The OTLP (gRPC) collector to export the traces of witness jobs to, such as
+    /// `http://localhost:4317`. Traces are not exported if it is not given.
+    #[cfg(feature = "otlp")]
+    #[clap(long = "otlp-endpoint", env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+}
+
+/// Flushes the pending spans to the collector when dropped.
+pub struct TracingGuard {
+    #[cfg(feature = "otlp")]
+    otlp_enabled: bool,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if self.otlp_enabled {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+pub fn init_tracing(args: &TelemetryArgs) -> Result<TracingGuard> {
+    let fmt_layer = match args.log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(fmt_layer);
+
+    #[cfg(feature = "otlp")]
+    let (registry, guard) = {
+        let otlp_layer = args.otlp_endpoint.as_deref().map(otlp::layer).transpose()?;
+        let guard = TracingGuard { otlp_enabled: otlp_layer.is_some() };
+        (registry.with(otlp_layer), guard)
+    };
+    #[cfg(not(feature = "otlp"))]
+    let guard = TracingGuard {};
+
+    registry.with(LevelFilter::INFO).init();
+
+    #[cfg(feature = "otlp")]
+    if let Some(endpoint) = &args.otlp_endpoint {
+        tracing::info!("Exporting traces to {:?}", endpoint);
+    }
+    Ok(guard)
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use anyhow::{anyhow, Result};
+    use opentelemetry::{trace::TracerProvider, KeyValue};
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{runtime, trace::Config, Resource};
+    use tracing::Subscriber;
+    use tracing_opentelemetry::OpenTelemetryLayer;
+    use tracing_subscriber::registry::LookupSpan;
+
+    const SERVICE_NAME: &str = "witness-gen-server";
+
+    pub fn layer<S>(
+        endpoint: &str,
+    ) -> Result<OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let provider =
+            opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+                .with_trace_config(Config::default().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", SERVICE_NAME),
+                ])))
+                .install_batch(runtime::Tokio)
+                .map_err(|e| anyhow!("Failed to install the OTLP exporter: {}", e))?;
+        let tracer = provider.tracer(SERVICE_NAME);
+        opentelemetry::global::set_tracer_provider(provider);
+
+        Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+    }
+}
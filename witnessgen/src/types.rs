@@ -1,7 +1,8 @@
 use alloy_primitives::B256;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Instant};
 
-use crate::{version::SP1_WITNESS_GEN_VERSION, VERIFICATION_KEY_HASH};
+use crate::{errors::JobError, version::SP1_WITNESS_GEN_VERSION, VERIFICATION_KEY_HASH};
 
 // NOTE(Ethan): If the SP1 SDK version check is removed from the `Kroma validator`,
 // delete the following code.
@@ -13,13 +14,35 @@ pub struct SpecResult {
     pub version: String,
     pub sp1_version: String,
     pub program_key: String,
+    /// The L2 chain IDs served in ascending order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chains: Vec<u64>,
+    /// The hash of the rollup config of the chain of the call. See `registry::rollup_config_hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollup_config_hash: Option<B256>,
 }
 
 impl SpecResult {
     pub fn new(version: String) -> Self {
         // NOTE(Ethan): We don’t want this v3 program key to be used
         // so we will temporarily set its value to an empty string.
-        Self { version, sp1_version: SP1_SDK_VERSION.to_string(), program_key: "".to_string() }
+        Self {
+            version,
+            sp1_version: SP1_SDK_VERSION.to_string(),
+            program_key: "".to_string(),
+            chains: vec![],
+            rollup_config_hash: None,
+        }
+    }
+
+    pub fn with_chains(mut self, chains: Vec<u64>) -> Self {
+        self.chains = chains;
+        self
+    }
+
+    pub fn with_rollup_config_hash(mut self, rollup_config_hash: B256) -> Self {
+        self.rollup_config_hash = Some(rollup_config_hash);
+        self
     }
 }
 
@@ -37,12 +60,54 @@ pub enum RequestResult {
     Failed,
 }
 
+/// The cycle counts of running a witness in the SP1 executor.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExecutionStats {
+    pub total_cycles: u64,
+    pub total_syscalls: u64,
+    /// The cycles of each section tracked by the program, e.g., `block-execution`.
+    pub cycle_tracker: BTreeMap<String, u64>,
+}
+
+/// The resources used by a job.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct JobResources {
+    /// The peak RSS of the `native_host_runner` subprocess of the job and its subprocesses in bytes.
+    pub peak_rss_bytes: u64,
+    /// The CPU time of the `native_host_runner` subprocess of the job and its subprocesses in
+    /// milliseconds.
+    pub cpu_time_ms: u64,
+    /// The bytes written to the data directory of the native host.
+    pub data_dir_bytes: u64,
+    /// The number of the RPC requests sent by the data fetcher of the job.
+    pub fetcher_rpc_calls: u64,
+}
+
+/// The information about the job which is stored with its witness.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct WitnessMetadata {
+    /// `None` if the simulation is skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<ExecutionStats>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<JobResources>,
+    /// The error of the job if it has failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JobError>,
+    /// The hash of the rollup config the witness was generated with, which the program derives from
+    /// its stdin. See `registry::rollup_config_hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollup_config_hash: Option<B256>,
+}
+
 /// The result of a witness method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct WitnessResult {
     pub status: RequestResult,
     pub program_key: String,
     pub witness: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<WitnessMetadata>,
 }
 
 impl Default for WitnessResult {
@@ -55,7 +120,17 @@ impl WitnessResult {
     pub const EMPTY_WITNESS: Vec<Vec<u8>> = Vec::new();
 
     pub fn new<T: ToString>(status: RequestResult, witness: T) -> Self {
-        Self { status, program_key: VERIFICATION_KEY_HASH.to_string(), witness: witness.to_string() }
+        Self {
+            status,
+            program_key: VERIFICATION_KEY_HASH.to_string(),
+            witness: witness.to_string(),
+            metadata: None,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: Option<WitnessMetadata>) -> Self {
+        self.metadata = metadata;
+        self
     }
 
     pub fn new_with_status(status: RequestResult) -> Self {
@@ -79,33 +154,57 @@ impl WitnessResult {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// A witness generation task. A single block task has `start_l2_hash` equal to `l2_hash`, while a
+/// range task covers every L2 block from `start_l2_hash` up to `l2_hash`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct TaskInfo {
+    pub start_l2_hash: B256,
     pub l2_hash: B256,
     pub l1_head_hash: B256,
 }
 
 impl TaskInfo {
-    pub fn set(&mut self, l2_hash: B256, l1_head_hash: B256) {
-        self.l2_hash = l2_hash;
-        self.l1_head_hash = l1_head_hash;
+    pub fn new(l2_hash: B256, l1_head_hash: B256) -> Self {
+        Self { start_l2_hash: l2_hash, l2_hash, l1_head_hash }
+    }
+
+    pub fn new_range(start_l2_hash: B256, end_l2_hash: B256, l1_head_hash: B256) -> Self {
+        Self { start_l2_hash, l2_hash: end_l2_hash, l1_head_hash }
+    }
+
+    pub fn is_range(&self) -> bool {
+        self.start_l2_hash != self.l2_hash
+    }
+
+    pub fn set(&mut self, task: &TaskInfo) {
+        *self = task.clone();
     }
 
     pub fn release(&mut self) {
-        self.l2_hash = B256::default();
-        self.l1_head_hash = B256::default();
+        *self = Self::default();
     }
 
-    pub fn is_equal(&self, l2_hash: B256, l1_head_hash: B256) -> bool {
-        self.l2_hash == l2_hash && self.l1_head_hash == l1_head_hash
+    pub fn is_equal(&self, task: &TaskInfo) -> bool {
+        self == task
     }
 
     pub fn is_empty(&self) -> bool {
-        let default_value = B256::default();
-        self.l2_hash == default_value && self.l1_head_hash == default_value
+        *self == Self::default()
     }
 }
 
+/// A task queued to the executor along with the span of the request which queued it.
+#[derive(Clone, Debug)]
+pub struct QueuedTask {
+    pub task: TaskInfo,
+    pub user_req_id: String,
+    pub span: tracing::Span,
+    /// The span of the wait in the queue, a child of `span`. It is closed when dropped by the
+    /// executor picking the task up.
+    pub queue_span: tracing::Span,
+    pub queued_at: Instant,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
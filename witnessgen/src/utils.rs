@@ -1,98 +1,282 @@
 use alloy_primitives::B256;
 use anyhow::Result;
-use op_succinct_host_utils::{
-    fetcher::{CacheMode, OPSuccinctDataFetcher},
-    get_proof_stdin,
-    witnessgen::WitnessGenExecutor,
-};
-use sp1_sdk::SP1Stdin;
-use std::{
-    fs::File,
-    io::Write,
-    panic::{self, AssertUnwindSafe},
-    sync::Arc,
-};
+use kona_host::HostCli;
+use op_succinct_host_utils::{fetcher::CacheMode, get_proof_stdin, witnessgen::WitnessGenExecutor};
+use serde_json::json;
+use sp1_sdk::{ProverClient, SP1Stdin};
+use std::{fs::File, io::Write, sync::Arc};
+use tracing::Instrument;
 
 use crate::{
-    types::{RequestResult, TaskInfo, WitnessResult},
+    chain::Chain,
+    checker::{check_range, Endpoint},
+    errors::WitnessGenError,
+    executor::JobConfig,
+    metrics::METRICS,
+    registry::stdin_rollup_config_hash,
+    resources::{dir_size, spawn_tracked, ProcessTreeSampler},
+    rpc_counter::RpcCounter,
+    status::StatusTracker,
+    types::{
+        ExecutionStats, JobResources, RequestResult, TaskInfo, WitnessMetadata, WitnessResult,
+    },
     witness_db::WitnessDB,
+    FAULT_PROOF_ELF,
 };
 
-#[allow(clippy::redundant_closure)]
-pub async fn generate_witness_impl(l2_hash: B256, l1_head_hash: B256) -> Result<SP1Stdin> {
-    let data_fetcher_future = panic::catch_unwind(AssertUnwindSafe(|| async {
-        OPSuccinctDataFetcher::new_with_rollup_config().await.unwrap()
-    }))
-    .map_err(|e| anyhow::anyhow!("Failed to create data fetcher: {:?}", e))?;
-    let data_fetcher = data_fetcher_future.await;
+pub async fn generate_witness_impl(
+    task: &TaskInfo,
+    chain: &Chain,
+    config: &JobConfig,
+    metadata: &mut WitnessMetadata,
+) -> Result<SP1Stdin, WitnessGenError> {
+    // NOTE: The resources are recorded even if the job fails, since a failure is often
+    // caused by the lack of them.
+    let resources = metadata.resources.insert(JobResources::default());
+    let host_cli = fetch_host_cli(task, chain, config, resources).await?;
+    run_native_host(&host_cli, &chain.status, resources).await?;
+    let sp1_stdin = build_stdin(&host_cli, &chain.status, config.rollup_config.hash)?;
+
+    if config.skip_simulation {
+        tracing::info!("Simulation has been skipped");
+    } else {
+        let execution = execute_witness(&host_cli, &chain.status, &sp1_stdin).await?;
+        let total_cycles = execution.total_cycles;
+        metadata.execution = Some(execution);
+        if let Some(max_cycles) = config.max_cycles {
+            if total_cycles > max_cycles {
+                return Err(WitnessGenError::exceeds_cycle_budget(total_cycles, max_cycles));
+            }
+        }
+    }
+
+    Ok(sp1_stdin)
+}
 
-    // Check the l2 block exists in the chain.
-    let l2_header = data_fetcher.get_l2_header(l2_hash.into()).await?;
+/// Checks the blocks of the task and prepares the host CLI args. The RPC requests of the data
+/// fetcher are counted even if it fails.
+#[tracing::instrument(name = "fetch", skip_all)]
+async fn fetch_host_cli(
+    task: &TaskInfo,
+    chain: &Chain,
+    config: &JobConfig,
+    resources: &mut JobResources,
+) -> Result<HostCli, WitnessGenError> {
+    let timer = METRICS.phase_duration.with_label_values(&["fetch"]).start_timer();
+    chain.status.enter_phase("fetch");
+    let rpc_counter = RpcCounter::start(&chain.endpoints)?;
+    let result = fetch_host_cli_with(task, config, &rpc_counter).await;
+    resources.fetcher_rpc_calls = rpc_counter.requests();
+    timer.observe_duration();
+
+    result
+}
+
+async fn fetch_host_cli_with(
+    task: &TaskInfo,
+    config: &JobConfig,
+    rpc_counter: &RpcCounter,
+) -> Result<HostCli, WitnessGenError> {
+    let data_fetcher =
+        config.rollup_config.fetcher_with(|endpoint| Ok(rpc_counter.url(endpoint)))?;
+
+    // Check the l2 blocks exist in the chain.
+    let l2_header = data_fetcher
+        .get_l2_header(task.l2_hash.into())
+        .instrument(tracing::info_span!("get_l2_header"))
+        .await
+        .map_err(|e| {
+            WitnessGenError::l2_block_not_found(e.to_string())
+                .with_params(json!({ "l2_hash": task.l2_hash }))
+        })?;
     let l2_number = l2_header.number;
+    let start_l2_number = if task.is_range() {
+        let start_l2_header = data_fetcher
+            .get_l2_header(task.start_l2_hash.into())
+            .instrument(tracing::info_span!("get_l2_header"))
+            .await
+            .map_err(|e| {
+                WitnessGenError::l2_block_not_found(e.to_string())
+                    .with_params(json!({ "l2_hash": task.start_l2_hash }))
+            })?;
+        start_l2_header.number
+    } else {
+        l2_number
+    };
+    let num_blocks = check_range(start_l2_number, l2_number, config.max_range_blocks)?;
 
     // Check the l1 block exists in the chain.
-    data_fetcher.get_l1_header(l1_head_hash.into()).await?;
+    data_fetcher
+        .get_l1_header(task.l1_head_hash.into())
+        .instrument(tracing::info_span!("get_l1_header"))
+        .await
+        .map_err(|e| {
+            WitnessGenError::l1_head_not_found(e.to_string())
+                .with_params(json!({ "l1_head_hash": task.l1_head_hash }))
+        })?;
 
     // Prepare the host CLI args.
+    let program_type = if num_blocks == 1 {
+        op_succinct_host_utils::ProgramType::Single
+    } else {
+        op_succinct_host_utils::ProgramType::Multi
+    };
     let mut host_cli = data_fetcher
-        .get_host_cli_args(
-            l2_number - 1,
-            l2_number,
-            op_succinct_host_utils::ProgramType::Single,
-            CacheMode::KeepCache,
-        )
-        .await?;
-    host_cli.l1_head = l1_head_hash;
+        .get_host_cli_args(start_l2_number - 1, l2_number, program_type, CacheMode::KeepCache)
+        .instrument(tracing::info_span!("get_host_cli_args"))
+        .await
+        .map_err(|e| WitnessGenError::rpc_unavailable(e.to_string()))?;
+    host_cli.l1_head = task.l1_head_hash;
+    host_cli.rollup_config_path = Some(config.rollup_config.path.clone());
+    // The native host fetches the preimages from the same URLs as the data fetcher, not through
+    // the proxy of `rpc_counter`, which stops after the data is fetched.
+    host_cli.l1_node_address = Some(rpc_counter.upstream(Endpoint::L1).to_string());
+    host_cli.l1_beacon_address = Some(rpc_counter.upstream(Endpoint::L1Beacon).to_string());
+    host_cli.l2_node_address = Some(rpc_counter.upstream(Endpoint::L2).to_string());
+
+    Ok(host_cli)
+}
 
-    // Start the server and native client.
+/// Runs the native host, which fetches the preimages of the task, and records its resources.
+#[tracing::instrument(name = "native_host", skip_all)]
+async fn run_native_host(
+    host_cli: &HostCli,
+    status: &StatusTracker,
+    resources: &mut JobResources,
+) -> Result<(), WitnessGenError> {
+    let timer = METRICS.phase_duration.with_label_values(&["native_host"]).start_timer();
+    status.enter_phase("native_host");
+    let data_dir_size = || host_cli.data_dir.as_deref().map(dir_size).unwrap_or(0);
+    let initial_data_dir_bytes = data_dir_size();
     let mut witnessgen_executor = WitnessGenExecutor::default();
-    witnessgen_executor.spawn_witnessgen(&host_cli).await?;
-    witnessgen_executor.flush().await?;
+    let (spawned, pids) = spawn_tracked(witnessgen_executor.spawn_witnessgen(host_cli)).await;
+    let sampler = ProcessTreeSampler::start(pids);
+    let result = match spawned {
+        Ok(_) => witnessgen_executor.flush().await,
+        Err(e) => Err(e),
+    };
+
+    let usage = sampler.stop().await;
+    timer.observe_duration();
+    resources.peak_rss_bytes = usage.peak_rss_bytes;
+    resources.cpu_time_ms = usage.cpu_time_ms;
+    resources.data_dir_bytes = data_dir_size().saturating_sub(initial_data_dir_bytes);
+    METRICS.job_peak_rss_bytes.observe(resources.peak_rss_bytes as f64);
+    METRICS.job_cpu_seconds.observe(resources.cpu_time_ms as f64 / 1000.0);
+    METRICS.job_data_dir_bytes.observe(resources.data_dir_bytes as f64);
+    tracing::info!("Native host resources: {:?}", resources);
 
+    result?;
+    Ok(())
+}
+
+/// Builds the stdin of the program, and checks the rollup config hash the program derives from it
+/// is `rollup_config_hash`, which is stored with the witness.
+#[tracing::instrument(name = "stdin", skip_all)]
+fn build_stdin(
+    host_cli: &HostCli,
+    status: &StatusTracker,
+    rollup_config_hash: B256,
+) -> Result<SP1Stdin, WitnessGenError> {
     // TODO(Ethan): currently, the versions are different between the witnessgen and the op-succinct. it can be simplified after updating the `sp1-sdk`.
+    let timer = METRICS.phase_duration.with_label_values(&["stdin"]).start_timer();
+    status.enter_phase("stdin");
     let sp1_stdin = {
-        let sp1_stdin_v3_4 = get_proof_stdin(&host_cli)
-            .map_err(|e| anyhow::anyhow!("Failed to get proof stdin: {:?}", e.to_string()))?;
+        let sp1_stdin_v3_4 = get_proof_stdin(host_cli).map_err(|e| {
+            WitnessGenError::internal(format!("Failed to get proof stdin: {:?}", e.to_string()))
+        })?;
         let mut sp1_stdin_v3_0 = SP1Stdin::default();
         sp1_stdin_v3_0.buffer = sp1_stdin_v3_4.buffer;
         sp1_stdin_v3_0
     };
+    let stdin_hash = stdin_rollup_config_hash(&sp1_stdin)?;
+    if stdin_hash != rollup_config_hash {
+        return Err(WitnessGenError::config_mismatch(format!(
+            "The rollup config hash of the stdin is {}, but the one of the server is {}",
+            stdin_hash, rollup_config_hash
+        )));
+    }
+    timer.observe_duration();
+    Ok(sp1_stdin)
+}
 
-    // TODO(Ethan): Uncomment this code block after updating `sp1-sdk`.
-    // if env::var("SKIP_SIMULATION").unwrap_or("false".to_string()) == "true" {
-    //     tracing::info!("Simulation has been started");
-    //     let executor = ProverClient::new();
-    //     let (_, report) = executor
-    //         .execute(FAULT_PROOF_ELF, sp1_stdin.clone())
-    //         .run()
-    //         .map_err(|e| anyhow::anyhow!("Failed to get proof stdin: {:?}", e.to_string()))?;
-    //     tracing::info!(
-    //         "successfully witness result generated - cycle: {:?}",
-    //         report.total_instruction_count()
-    //     );
-    // }
+/// Runs the program on the stdin in the SP1 executor and checks the committed values against the
+/// claim of the host CLI args.
+///
+/// TODO: The executor of `sp1-sdk` 3.x is not known to run the program built with `sp1-build` 4.x,
+/// so the simulation is skipped by default. Run it by default after updating `sp1-sdk`.
+#[tracing::instrument(name = "execute", skip_all)]
+async fn execute_witness(
+    host_cli: &HostCli,
+    status: &StatusTracker,
+    sp1_stdin: &SP1Stdin,
+) -> Result<ExecutionStats, WitnessGenError> {
+    let timer = METRICS.phase_duration.with_label_values(&["execute"]).start_timer();
+    status.enter_phase("execute");
+    let stdin = sp1_stdin.clone();
+    let (mut public_values, report) = tokio::task::spawn_blocking(move || {
+        ProverClient::new().execute(FAULT_PROOF_ELF, stdin).run()
+    })
+    .await
+    .map_err(|e| WitnessGenError::internal(format!("Failed to join the executor: {}", e)))?
+    .map_err(|e| {
+        WitnessGenError::execution_failed(format!("Failed to execute the witness: {}", e))
+    })?;
+    timer.observe_duration();
 
-    Ok(sp1_stdin)
+    let stats = ExecutionStats {
+        total_cycles: report.total_instruction_count(),
+        total_syscalls: report.total_syscall_count(),
+        cycle_tracker: report.cycle_tracker.into_iter().collect(),
+    };
+    tracing::info!("successfully witness executed - cycle: {:?}", stats.total_cycles);
+
+    // NOTE: The program commits the parent output root, the output root and the L1 head in
+    // this order.
+    let parent_output_root: B256 = public_values.read();
+    let output_root: B256 = public_values.read();
+    let l1_head: B256 = public_values.read();
+    if parent_output_root != host_cli.agreed_l2_output_root
+        || output_root != host_cli.claimed_l2_output_root
+        || l1_head != host_cli.l1_head
+    {
+        return Err(WitnessGenError::execution_failed(
+            "The committed values do not match the claim".to_string(),
+        )
+        .with_params(json!({
+            "committed": {
+                "parent_output_root": parent_output_root,
+                "output_root": output_root,
+                "l1_head": l1_head,
+            },
+            "claimed": {
+                "parent_output_root": host_cli.agreed_l2_output_root,
+                "output_root": host_cli.claimed_l2_output_root,
+                "l1_head": host_cli.l1_head,
+            },
+        })));
+    }
+
+    Ok(stats)
 }
 
 pub fn get_status_by_local_id(
     current_task: &mut TaskInfo,
     witness_db: Arc<WitnessDB>,
-    l2_hash: &B256,
-    l1_head_hash: &B256,
+    task: &TaskInfo,
     is_mutable: bool,
 ) -> Result<RequestResult> {
     // `idle` is `true` if the witness generator is in idle (i.e., current task is empty).
     let idle = current_task.is_empty();
-    // `processing` is `true` if the request with local id (`l2_hash` and `l1_head_hash`) is in progress.
-    let processing = current_task.is_equal(*l2_hash, *l1_head_hash);
+    // `processing` is `true` if the request with local id (`task`) is in progress.
+    let processing = current_task.is_equal(task);
 
     // `found_witness` is `true` if the witness is found from the db.
     let mut found_witness = false;
     // `meaningful_witness` is `true` if the found witness does not equals to `WitnessResult::EMPTY_WITNESS`.
     // Note that if the witness equals to `WitnessResult::EMPTY_WITNESS` implies that the previous task has been failed.
     let mut meaningful_witness = false;
-    let witness = witness_db.get(l2_hash, l1_head_hash);
+    let witness = witness_db.get(task);
     if let Some(witness) = witness {
         found_witness = true;
         if !witness.is_empty() {
@@ -108,8 +292,8 @@ pub fn get_status_by_local_id(
     // If there is no currently running task.
     if idle && found_witness {
         if is_mutable {
-            println!("db remove: {:#?}", l2_hash);
-            witness_db.remove(l2_hash, l1_head_hash).unwrap();
+            tracing::info!("db remove: {:?}", task.l2_hash);
+            witness_db.remove(task).unwrap();
         }
         return Ok(RequestResult::Failed);
     } else if idle && !found_witness {
@@ -121,8 +305,8 @@ pub fn get_status_by_local_id(
     // If there is a currently running task.
     if processing && found_witness {
         if is_mutable {
-            println!("db remove: {:#?}", l2_hash);
-            witness_db.remove(l2_hash, l1_head_hash).unwrap();
+            tracing::info!("db remove: {:?}", task.l2_hash);
+            witness_db.remove(task).unwrap();
         }
         Ok(RequestResult::Failed)
     } else if processing && !found_witness {
@@ -138,6 +322,6 @@ pub fn save_witness(witness_data: &String, witness_result: &WitnessResult) -> Re
     let witness_json = serde_json::to_string_pretty(&witness_result)?;
     let mut file = File::create(witness_data)?;
     file.write_all(witness_json.as_bytes())?;
-    println!("Witness was saved");
+    tracing::info!("Witness was saved");
     Ok(())
 }
@@ -1,43 +1,261 @@
-use alloy_primitives::B256;
 use anyhow::{anyhow, Result};
 use kroma_zkvm_common::db::FileDB;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-static CAPACITY: usize = 10;
-static VALUE_EXPIRING_SECS: usize = 24 * 60 * 60; // 86400; A day in seconds.
+use crate::{
+    audit::{AuditEvent, AuditEventKind, AuditLog},
+    metrics::METRICS,
+    resources::dir_size,
+    types::{TaskInfo, WitnessMetadata},
+};
+
+pub static DEFAULT_CAPACITY: usize = 10;
+pub static DEFAULT_VALUE_EXPIRING_SECS: usize = 24 * 60 * 60; // 86400; A day in seconds.
 
 pub struct WitnessDB {
     db: FileDB,
+    path: PathBuf,
+    capacity: usize,
+    value_expiring: Duration,
+    // The metadata of each witness is kept as a JSON file in this directory, since `FileDB` only
+    // holds the witnesses.
+    metadata_path: PathBuf,
+    // The keys written by this process. It is used to count the entries and the evictions without
+    // reading the store.
+    keys: Mutex<HashMap<Vec<u8>, StoredKey>>,
+    audit_log: AuditLog,
+    // The bytes last added to the gauge, which is shared by the stores of every chain.
+    reported_bytes: AtomicI64,
+}
+
+struct StoredKey {
+    task: TaskInfo,
+    stored_at: Instant,
 }
 
 impl WitnessDB {
     pub fn new(db_file_path: &str) -> Self {
-        Self { db: FileDB::new(db_file_path.into(), CAPACITY, VALUE_EXPIRING_SECS) }
+        Self::with_capacity(db_file_path, DEFAULT_CAPACITY, DEFAULT_VALUE_EXPIRING_SECS)
+    }
+
+    /// Opens the store keeping up to `capacity` witnesses for `value_expiring_secs` each.
+    pub fn with_capacity(db_file_path: &str, capacity: usize, value_expiring_secs: usize) -> Self {
+        let path = PathBuf::from(db_file_path);
+        // NOTE: The audit log is kept next to the store, e.g., `data/audit` for
+        // `data/witness_store`, so that it outlives the entries of the store.
+        let audit_log = AuditLog::new(path.with_file_name("audit"));
+        let db = Self {
+            db: FileDB::new(db_file_path.into(), capacity, value_expiring_secs),
+            metadata_path: path.with_file_name("witness_metadata"),
+            path,
+            capacity,
+            value_expiring: Duration::from_secs(value_expiring_secs as u64),
+            keys: Mutex::new(HashMap::new()),
+            audit_log,
+            reported_bytes: AtomicI64::new(0),
+        };
+        db.refresh_size();
+        db
     }
 
-    fn build_key(l2_hash: &B256, l1_head_hash: &B256) -> Vec<u8> {
-        let mut key = Vec::with_capacity(64);
-        key.extend_from_slice(l2_hash.as_slice());
-        key.extend_from_slice(l1_head_hash.as_slice());
+    // NOTE: A range witness is keyed by its first block as well, so that it never collides
+    // with the single block witness of its last block.
+    fn build_key(task: &TaskInfo) -> Vec<u8> {
+        let mut key = Vec::with_capacity(96);
+        if task.is_range() {
+            key.extend_from_slice(task.start_l2_hash.as_slice());
+        }
+        key.extend_from_slice(task.l2_hash.as_slice());
+        key.extend_from_slice(task.l1_head_hash.as_slice());
         key
     }
 
-    pub fn get(&self, l2_hash: &B256, l1_head_hash: &B256) -> Option<Vec<Vec<u8>>> {
-        let key = Self::build_key(l2_hash, l1_head_hash);
-        self.db.get(&key)
+    pub fn get(&self, task: &TaskInfo) -> Option<Vec<Vec<u8>>> {
+        let key = Self::build_key(task);
+        let witness = self.db.get(&key);
+        if witness.is_none() {
+            // The store has dropped the witness before this process expected it to.
+            let mut keys = self.keys.lock().unwrap();
+            if let Some(stored) = keys.remove(&key) {
+                self.evict(&key, &stored.task);
+            }
+        }
+        witness
+    }
+
+    pub fn set(&self, task: &TaskInfo, witness_buf: Vec<Vec<u8>>) -> Result<()> {
+        let key = Self::build_key(task);
+        self.db.set(&key, &witness_buf).map_err(|e| anyhow!("Failed to set witness: {}", e))?;
+        self.remove_metadata(&key);
+        {
+            let mut keys = self.keys.lock().unwrap();
+            let stored = StoredKey { task: task.clone(), stored_at: Instant::now() };
+            if keys.insert(key, stored).is_none() {
+                METRICS.db_entries.inc();
+            }
+            for key in stale_keys(&keys, self.capacity, self.value_expiring, Instant::now()) {
+                if let Some(stored) = keys.remove(&key) {
+                    self.evict(&key, &stored.task);
+                }
+            }
+        }
+        self.refresh_size();
+        Ok(())
+    }
+
+    pub fn remove(&self, task: &TaskInfo) -> Result<()> {
+        let key = Self::build_key(task);
+        self.db.remove(&key).map_err(|e| anyhow!("Failed to remove witness: {}", e))?;
+        self.remove_metadata(&key);
+        if self.keys.lock().unwrap().remove(&key).is_some() {
+            METRICS.db_entries.dec();
+        }
+        self.audit_log.record(AuditEvent::new(AuditEventKind::Removed, task));
+        self.refresh_size();
+        Ok(())
+    }
+
+    fn metadata_file(&self, key: &[u8]) -> PathBuf {
+        self.metadata_path.join(format!("{}.json", hex::encode(key)))
+    }
+
+    pub fn get_metadata(&self, task: &TaskInfo) -> Option<WitnessMetadata> {
+        let file = self.metadata_file(&Self::build_key(task));
+        let bytes = std::fs::read(file).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Stores the metadata of the witness. It must be called after `set`, which drops the
+    /// metadata of the previous witness.
+    pub fn set_metadata(&self, task: &TaskInfo, metadata: &WitnessMetadata) -> Result<()> {
+        let file = self.metadata_file(&Self::build_key(task));
+        let bytes = serde_json::to_vec(metadata)?;
+        std::fs::create_dir_all(&self.metadata_path)
+            .and_then(|_| std::fs::write(file, bytes))
+            .map_err(|e| anyhow!("Failed to set witness metadata: {}", e))
     }
 
-    pub fn set(
-        &self,
-        l2_hash: &B256,
-        l1_head_hash: &B256,
-        witness_buf: Vec<Vec<u8>>,
-    ) -> Result<()> {
-        let key = Self::build_key(l2_hash, l1_head_hash);
-        self.db.set(&key, &witness_buf).map_err(|e| anyhow!("Failed to set witness: {}", e))
+    fn remove_metadata(&self, key: &[u8]) {
+        let _ = std::fs::remove_file(self.metadata_file(key));
     }
 
-    pub fn remove(&self, l2_hash: &B256, l1_head_hash: &B256) -> Result<()> {
-        let key = Self::build_key(l2_hash, l1_head_hash);
-        self.db.remove(&key).map_err(|e| anyhow!("Failed to remove witness: {}", e))
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit_log
+    }
+
+    /// Returns the number of witnesses written by this process which are still stored.
+    pub fn num_entries(&self) -> usize {
+        self.keys.lock().unwrap().len()
+    }
+
+    /// Returns the maximum number of witnesses kept by the store.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the size of the store on disk in bytes.
+    pub fn size_in_bytes(&self) -> u64 {
+        dir_size(&self.path)
+    }
+
+    /// Checks if the store is writable by writing and removing a probe file next to it.
+    pub fn check_writable(&self) -> Result<()> {
+        let dir = if self.path.is_dir() {
+            self.path.clone()
+        } else {
+            self.path.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+        let probe = dir.join(".witness_db_probe");
+        std::fs::write(&probe, b"probe")
+            .and_then(|_| std::fs::remove_file(&probe))
+            .map_err(|e| anyhow!("{} is not writable: {}", dir.display(), e))
+    }
+
+    // Records the eviction of the witness of `key`, which has been dropped from `keys`.
+    fn evict(&self, key: &[u8], task: &TaskInfo) {
+        self.remove_metadata(key);
+        self.audit_log.record(AuditEvent::new(AuditEventKind::Evicted, task));
+        METRICS.db_evictions.inc();
+        METRICS.db_entries.dec();
+    }
+
+    fn refresh_size(&self) {
+        let bytes = self.size_in_bytes() as i64;
+        METRICS.db_bytes.add(bytes - self.reported_bytes.swap(bytes, Ordering::SeqCst));
+    }
+}
+
+// Returns the keys `FileDB` has evicted by the expiration or the capacity, the oldest first, so
+// that the evictions are counted without reading the witnesses.
+fn stale_keys(
+    keys: &HashMap<Vec<u8>, StoredKey>,
+    capacity: usize,
+    value_expiring: Duration,
+    now: Instant,
+) -> Vec<Vec<u8>> {
+    let mut by_age: Vec<(&Vec<u8>, Instant)> =
+        keys.iter().map(|(key, stored)| (key, stored.stored_at)).collect();
+    by_age.sort_by_key(|(_, stored_at)| *stored_at);
+    let num_over_capacity = by_age.len().saturating_sub(capacity);
+    by_age
+        .into_iter()
+        .enumerate()
+        .filter(|(index, (_, stored_at))| {
+            *index < num_over_capacity || now.duration_since(*stored_at) >= value_expiring
+        })
+        .map(|(_, (key, _))| key.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::b256;
+
+    use super::*;
+
+    #[test]
+    fn test_range_key_differs_from_single_key() {
+        let start = b256!("0000000000000000000000000000000000000000000000000000000000000001");
+        let end = b256!("0000000000000000000000000000000000000000000000000000000000000002");
+        let l1_head = b256!("0000000000000000000000000000000000000000000000000000000000000003");
+
+        let single_key = WitnessDB::build_key(&TaskInfo::new(end, l1_head));
+        let range_key = WitnessDB::build_key(&TaskInfo::new_range(start, end, l1_head));
+        assert_eq!(single_key.len(), 64);
+        assert_eq!(range_key.len(), 96);
+
+        // A range of one block is the same as a single block witness.
+        let one_block_range_key = WitnessDB::build_key(&TaskInfo::new_range(end, end, l1_head));
+        assert_eq!(single_key, one_block_range_key);
+    }
+
+    #[test]
+    fn test_stale_keys() {
+        let now = Instant::now();
+        let task = TaskInfo::default();
+        let keys: HashMap<Vec<u8>, StoredKey> = (0..4u8)
+            .map(|i| {
+                let stored_at = now - Duration::from_secs(40 - i as u64 * 10);
+                (vec![i], StoredKey { task: task.clone(), stored_at })
+            })
+            .collect();
+
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert!(stale_keys(&keys, 4, day, now).is_empty());
+        assert_eq!(stale_keys(&keys, 2, day, now), vec![vec![0], vec![1]]);
+        // The keys stored 40 and 30 seconds ago have expired.
+        assert_eq!(stale_keys(&keys, 4, Duration::from_secs(30), now), vec![vec![0], vec![1]]);
+        assert_eq!(
+            stale_keys(&keys, 1, Duration::from_secs(35), now),
+            vec![vec![0], vec![1], vec![2]]
+        );
     }
 }
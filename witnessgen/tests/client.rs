@@ -1,7 +1,7 @@
 use alloy_primitives::B256;
 use anyhow::Result;
 use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
-use jsonrpsee_core::{client::ClientT, rpc_params};
+use jsonrpsee_core::{client::ClientT, error::CallError, rpc_params, Error as RpcError};
 
 use kroma_witnessgen::{
     errors::WitnessGenError,
@@ -60,12 +60,11 @@ impl TestClient {
         let params = rpc_params![l2_hash, l1_head_hash];
         match self.witnessgen_client.request("requestWitness", params).await {
             Ok(result) => Ok(result),
-            Err(e) if e.to_string().contains("Another request is in progress") => {
-                Err(WitnessGenError::already_in_progress(e.to_string()))
-            }
-            Err(e) if e.to_string().contains("Invalid parameters") => {
-                Err(WitnessGenError::invalid_input_hash("Invalid parameters".to_string()))
-            }
+            Err(RpcError::Call(CallError::Custom(e))) => Err(WitnessGenError::from_json_error(
+                e.code() as i64,
+                e.message().to_string(),
+                e.data().and_then(|data| serde_json::from_str(data.get()).ok()),
+            )),
             Err(e) => panic!("Unexpected error: {}", e),
         }
     }